@@ -0,0 +1,424 @@
+//! This module contains everything related to the escape sequences written by the commands.
+
+/// A color of the terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// One of the 256 indexed colors, the first 16 being the standard and bright colors.
+    Indexed(u8),
+
+    /// A truecolor.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Writes the SGR parameters that select this color, `base` being 30 for foreground and 40
+    /// for background.
+    fn push_sgr(&self, buffer: &mut String, base: u8) {
+        match *self {
+            Color::Indexed(i) if i < 8 => buffer.push_str(&format!(";{}", base + i)),
+            Color::Indexed(i) if i < 16 => buffer.push_str(&format!(";{}", base + 60 + i - 8)),
+            Color::Indexed(i) => buffer.push_str(&format!(";{};5;{}", base + 8, i)),
+            Color::Rgb(r, g, b) => buffer.push_str(&format!(";{};2;{};{};{}", base + 8, r, g, b)),
+        }
+    }
+}
+
+/// The colors and attributes of the text, as set by SGR sequences (`\x1b[...m`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    /// The foreground color, `None` being the default color of the terminal.
+    pub fg: Option<Color>,
+
+    /// The background color, `None` being the default color of the terminal.
+    pub bg: Option<Color>,
+
+    /// Whether the text is bold.
+    pub bold: bool,
+
+    /// Whether the text is dim.
+    pub dim: bool,
+
+    /// Whether the text is in italic.
+    pub italic: bool,
+
+    /// Whether the text is underlined.
+    pub underline: bool,
+
+    /// Whether the text blinks.
+    pub blink: bool,
+
+    /// Whether the foreground and background colors are swapped.
+    pub reverse: bool,
+
+    /// Whether the text is hidden.
+    pub hidden: bool,
+
+    /// Whether the text is crossed out.
+    pub strikethrough: bool,
+}
+
+impl Style {
+    /// Updates the style from the parameters of an SGR sequence.
+    pub fn apply(&mut self, params: &str) {
+        // Each parameter can have sub-parameters separated by colons, e.g. `38:2::255:0:0`
+        let params = params
+            .split(';')
+            .map(|p| {
+                p.split(':')
+                    .map(|x| x.parse::<u32>().ok())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut iter = params.iter();
+
+        while let Some(param) = iter.next() {
+            match param[0].unwrap_or(0) {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = param.get(1) != Some(&Some(0)),
+                5 | 6 => self.blink = true,
+                7 => self.reverse = true,
+                8 => self.hidden = true,
+                9 => self.strikethrough = true,
+                21 => self.underline = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                28 => self.hidden = false,
+                29 => self.strikethrough = false,
+                x @ 30..=37 => self.fg = Some(Color::Indexed(x as u8 - 30)),
+                38 => self.fg = extended_color(param, &mut iter).or(self.fg),
+                39 => self.fg = None,
+                x @ 40..=47 => self.bg = Some(Color::Indexed(x as u8 - 40)),
+                48 => self.bg = extended_color(param, &mut iter).or(self.bg),
+                49 => self.bg = None,
+                x @ 90..=97 => self.fg = Some(Color::Indexed(x as u8 - 90 + 8)),
+                x @ 100..=107 => self.bg = Some(Color::Indexed(x as u8 - 100 + 8)),
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns the SGR sequence that sets the terminal to this style, whatever its current style.
+    pub fn sgr(&self) -> String {
+        let mut buffer = String::from("\x1b[0");
+
+        for (enabled, code) in [
+            (self.bold, 1),
+            (self.dim, 2),
+            (self.italic, 3),
+            (self.underline, 4),
+            (self.blink, 5),
+            (self.reverse, 7),
+            (self.hidden, 8),
+            (self.strikethrough, 9),
+        ] {
+            if enabled {
+                buffer.push_str(&format!(";{}", code));
+            }
+        }
+
+        if let Some(fg) = self.fg {
+            fg.push_sgr(&mut buffer, 30);
+        }
+
+        if let Some(bg) = self.bg {
+            bg.push_sgr(&mut buffer, 40);
+        }
+
+        buffer.push('m');
+        buffer
+    }
+}
+
+/// Reads the color of a 38 or 48 SGR parameter, either from its sub-parameters
+/// (`38:5:n`, `38:2::r:g:b`) or from the following parameters (`38;5;n`, `38;2;r;g;b`).
+fn extended_color<'a, I>(param: &[Option<u32>], iter: &mut I) -> Option<Color>
+where
+    I: Iterator<Item = &'a Vec<Option<u32>>>,
+{
+    let values = if param.len() > 1 {
+        let mut values = param[1..].to_vec();
+
+        // The color space identifier of `38:2:id:r:g:b` is optional
+        if values.first() == Some(&Some(2)) && values.len() == 5 {
+            values.remove(1);
+        }
+
+        values
+    } else {
+        let mut values = vec![iter.next()?[0]];
+        let count = match values[0] {
+            Some(5) => 1,
+            Some(2) => 3,
+            _ => 0,
+        };
+        for _ in 0..count {
+            values.push(iter.next()?[0]);
+        }
+        values
+    };
+
+    let byte = |i: usize| values.get(i).copied().flatten().map(|x| x.min(255) as u8);
+
+    match values.first() {
+        Some(Some(5)) => Some(Color::Indexed(byte(1)?)),
+        Some(Some(2)) => Some(Color::Rgb(
+            byte(1).unwrap_or(0),
+            byte(2).unwrap_or(0),
+            byte(3).unwrap_or(0),
+        )),
+        _ => None,
+    }
+}
+
+/// What a sequence of characters written by a command means.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// A character that is displayed.
+    Print(char),
+
+    /// A control character, e.g. a line feed, a carriage return or a tab.
+    Control(char),
+
+    /// A control sequence, e.g. `\x1b[1;31m` or `\x1b[K`.
+    Csi {
+        /// The whole sequence, including the escape character.
+        raw: String,
+
+        /// The parameters of the sequence, between the `[` and the final character.
+        params: String,
+
+        /// The final character of the sequence, which tells what the sequence does.
+        end: char,
+    },
+
+    /// Any other escape sequence, e.g. an OSC sequence changing the title of the terminal.
+    Other(String),
+}
+
+impl Action {
+    /// Returns whether the action is an SGR sequence, i.e. a sequence that changes the style.
+    pub fn is_sgr(&self) -> bool {
+        match self {
+            Action::Csi { params, end, .. } => {
+                *end == 'm' && !params.starts_with(['<', '=', '>', '?'])
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The state of the parser.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum State {
+    /// Not in an escape sequence.
+    #[default]
+    Ground,
+
+    /// Right after an escape character.
+    Escape,
+
+    /// In an escape sequence with intermediate characters, e.g. `\x1b(B`.
+    EscapeIntermediate,
+
+    /// In a control sequence.
+    Csi,
+
+    /// In a string sequence (OSC, DCS...) which ends with BEL or ST.
+    String,
+
+    /// In a string sequence, right after an escape character which may start the ST.
+    StringEscape,
+}
+
+/// The maximum length of a string sequence; longer sequences are truncated.
+const MAX_STRING_LEN: usize = 4096;
+
+/// A streaming parser for the output of the commands.
+///
+/// Characters are fed one by one, so that escape sequences split between two reads are still
+/// recognized.
+#[derive(Clone, Debug, Default)]
+pub struct Parser {
+    /// The current state of the parser.
+    state: State,
+
+    /// The escape sequence being parsed.
+    buffer: String,
+}
+
+impl Parser {
+    /// Creates a new parser.
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    /// Feeds a character to the parser, and returns the action if the character completes one.
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        if c == '\x1b' && self.state != State::String {
+            self.state = State::Escape;
+            self.buffer = String::from(c);
+            return None;
+        }
+
+        match self.state {
+            State::Ground => {
+                if c.is_control() {
+                    Some(Action::Control(c))
+                } else {
+                    Some(Action::Print(c))
+                }
+            }
+
+            State::Escape => {
+                self.buffer.push(c);
+                match c {
+                    '[' => self.state = State::Csi,
+                    ']' | 'P' | 'X' | '^' | '_' => self.state = State::String,
+                    '\x20'..='\x2f' => self.state = State::EscapeIntermediate,
+                    _ => return Some(self.finish_other()),
+                }
+                None
+            }
+
+            State::EscapeIntermediate => {
+                self.buffer.push(c);
+                if ('\x30'..='\x7e').contains(&c) {
+                    return Some(self.finish_other());
+                }
+                None
+            }
+
+            State::Csi => {
+                // Control characters abort the sequence and are executed
+                if c.is_ascii_control() {
+                    self.state = State::Ground;
+                    self.buffer.clear();
+                    return Some(Action::Control(c));
+                }
+
+                self.buffer.push(c);
+
+                if ('\x40'..='\x7e').contains(&c) {
+                    self.state = State::Ground;
+                    let raw = std::mem::take(&mut self.buffer);
+                    let params = raw[2..raw.len() - 1].to_string();
+                    return Some(Action::Csi {
+                        raw,
+                        params,
+                        end: c,
+                    });
+                }
+
+                None
+            }
+
+            State::String => {
+                match c {
+                    '\x07' => {
+                        self.buffer.push(c);
+                        return Some(self.finish_other());
+                    }
+                    '\x1b' => self.state = State::StringEscape,
+                    _ if self.buffer.len() < MAX_STRING_LEN => self.buffer.push(c),
+                    _ => (),
+                }
+                None
+            }
+
+            State::StringEscape => {
+                if c == '\\' {
+                    self.buffer.push_str("\x1b\\");
+                    return Some(self.finish_other());
+                }
+
+                // The escape character was not a string terminator but the start of a new sequence
+                self.state = State::Escape;
+                self.buffer = String::from('\x1b');
+                self.advance(c)
+            }
+        }
+    }
+
+    /// Ends an escape sequence that we don't interpret.
+    fn finish_other(&mut self) -> Action {
+        self.state = State::Ground;
+        Action::Other(std::mem::take(&mut self.buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the style set by the parameters of an SGR sequence.
+    fn style(params: &str) -> Style {
+        let mut style = Style::default();
+        style.apply(params);
+        style
+    }
+
+    #[test]
+    fn standard_colors() {
+        assert_eq!(style("31").fg, Some(Color::Indexed(1)));
+        assert_eq!(style("44").bg, Some(Color::Indexed(4)));
+        assert_eq!(style("92").fg, Some(Color::Indexed(10)));
+        assert_eq!(style("103").bg, Some(Color::Indexed(11)));
+        assert_eq!(style("31;39").fg, None);
+    }
+
+    #[test]
+    fn indexed_colors() {
+        assert_eq!(style("38;5;208").fg, Some(Color::Indexed(208)));
+        assert_eq!(style("48:5:17").bg, Some(Color::Indexed(17)));
+
+        // The parameters after the color are still applied
+        let s = style("38;5;208;1");
+        assert_eq!(s.fg, Some(Color::Indexed(208)));
+        assert!(s.bold);
+    }
+
+    #[test]
+    fn truecolors() {
+        assert_eq!(style("38;2;1;2;3").fg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(style("38:2::1:2:3").fg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(style("38:2:1:2:3").fg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(style("48:2:0:1:2:3").bg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(style("38;2;300;2;3").fg, Some(Color::Rgb(255, 2, 3)));
+    }
+
+    #[test]
+    fn incomplete_colors() {
+        assert_eq!(style("31;38;5").fg, Some(Color::Indexed(1)));
+        assert_eq!(style("31;38").fg, Some(Color::Indexed(1)));
+    }
+
+    #[test]
+    fn attributes() {
+        let s = style("1;3;4;9");
+        assert!(s.bold && s.italic && s.underline && s.strikethrough);
+
+        assert!(!style("4:0").underline);
+        assert!(!style("1;2;22").bold);
+        assert_eq!(style("1;31;0"), Style::default());
+        assert_eq!(style(""), Style::default());
+    }
+
+    #[test]
+    fn sgr_round_trip() {
+        for params in ["1;2;3;4;5;7;8;9", "31;102", "38;5;208;48;2;1;2;3", ""] {
+            let s = style(params);
+            let sgr = s.sgr();
+            assert_eq!(style(&sgr[2..sgr.len() - 1]), s);
+        }
+    }
+}
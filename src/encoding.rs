@@ -0,0 +1,131 @@
+//! This module contains the decoding of the bytes written by the commands.
+
+use std::str::FromStr;
+
+/// The encoding of the output of a command.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, used by most programs.
+    #[default]
+    Utf8,
+
+    /// Latin-1 (ISO-8859-1), used by some legacy tools.
+    Latin1,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Encoding, String> {
+        match s.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            _ => Err(format!("unknown encoding: {}", s)),
+        }
+    }
+}
+
+/// A streaming decoder for the output of a command.
+///
+/// Multi-byte characters can be split between two reads, so incomplete sequences are kept until
+/// the next read instead of being replaced by U+FFFD.
+pub struct Decoder {
+    /// The encoding of the bytes.
+    encoding: Encoding,
+
+    /// The bytes of an incomplete character of the previous read.
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    /// Creates a new decoder.
+    pub fn new(encoding: Encoding) -> Decoder {
+        Decoder {
+            encoding,
+            pending: vec![],
+        }
+    }
+
+    /// Decodes the bytes of a read.
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        match self.encoding {
+            Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            Encoding::Utf8 => {
+                self.pending.extend_from_slice(bytes);
+
+                let mut output = String::new();
+                let mut start = 0;
+
+                loop {
+                    match std::str::from_utf8(&self.pending[start..]) {
+                        Ok(s) => {
+                            output.push_str(s);
+                            start = self.pending.len();
+                            break;
+                        }
+
+                        Err(e) => {
+                            let valid = &self.pending[start..start + e.valid_up_to()];
+                            output.push_str(std::str::from_utf8(valid).unwrap());
+                            start += e.valid_up_to();
+
+                            match e.error_len() {
+                                Some(len) => {
+                                    output.push(char::REPLACEMENT_CHARACTER);
+                                    start += len;
+                                }
+
+                                // The end of the bytes is the beginning of a character
+                                None => break,
+                            }
+                        }
+                    }
+                }
+
+                self.pending.drain(0..start);
+                output
+            }
+        }
+    }
+
+    /// Decodes the remaining bytes, once there is nothing left to read.
+    pub fn finish(&mut self) -> String {
+        if self.pending.is_empty() {
+            String::new()
+        } else {
+            self.pending.clear();
+            char::REPLACEMENT_CHARACTER.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn character_split_between_reads() {
+        let mut decoder = Decoder::new(Encoding::Utf8);
+        let bytes = "a€b".as_bytes();
+
+        assert_eq!(decoder.decode(&bytes[..2]), "a");
+        assert_eq!(decoder.decode(&bytes[2..3]), "");
+        assert_eq!(decoder.decode(&bytes[3..]), "€b");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let mut decoder = Decoder::new(Encoding::Utf8);
+
+        assert_eq!(decoder.decode(b"a\xffb"), "a\u{fffd}b");
+        assert_eq!(decoder.decode(b"c\xe2\x82"), "c");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+
+    #[test]
+    fn latin1() {
+        let mut decoder = Decoder::new(Encoding::Latin1);
+        assert_eq!(decoder.decode(b"caf\xe9"), "café");
+    }
+}
@@ -0,0 +1,337 @@
+//! This module contains the export of the tiles to HTML.
+//!
+//! The output is interpreted the same way as when it is rendered in the terminal, and each run
+//! of text with the same style becomes a styled span.
+
+use crate::ansi::{Color, Style};
+use crate::line::{Line, Marker};
+use crate::tile::{Row, Tile};
+
+/// The default colors of the text and of the background.
+const DEFAULT_COLORS: (&str, &str) = ("#e5e5e5", "#000000");
+
+/// The style sheet of the exported documents.
+const STYLE_SHEET: &str = "body { background: #000000; color: #e5e5e5; font-family: monospace; }
+.tiles { display: grid; }
+.tile { border: 1px solid #7f7f7f; margin: 2px; overflow-x: auto; }
+.title { border-bottom: 1px solid #7f7f7f; padding: 0 1ch; white-space: pre; }
+pre { margin: 0; padding: 0 1ch; font-family: inherit; }";
+
+/// Returns a document showing the tiles in the same grid as in the terminal, with the rows they
+/// show or with their whole scrollback if `full` is true.
+pub fn document(tiles: &[&Tile], full: bool) -> String {
+    // The grid lines are the edges of the tiles
+    let mut columns = vec![];
+    let mut rows = vec![];
+    for tile in tiles {
+        let ((x, y), (w, h)) = (tile.outer_position, tile.outer_size);
+        columns.extend([x, x + w]);
+        rows.extend([y, y + h]);
+    }
+    for lines in [&mut columns, &mut rows] {
+        lines.sort_unstable();
+        lines.dedup();
+    }
+
+    let line = |lines: &[u16], start: u16| lines.iter().position(|l| *l == start).unwrap() + 1;
+
+    // The columns are as wide as in the terminal, the rows grow with the content
+    let widths = columns
+        .windows(2)
+        .map(|w| format!("{}ch", w[1] - w[0]))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut buffer = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>tileview</title>\n\
+         <style>\n{}\n</style>\n</head>\n<body>\n\
+         <div class=\"tiles\" style=\"grid-template-columns: {}\">\n",
+        STYLE_SHEET, widths
+    );
+
+    for tile in tiles {
+        let ((x, y), (w, h)) = (tile.outer_position, tile.outer_size);
+
+        buffer.push_str(&format!(
+            "<div class=\"tile\" style=\"grid-column: {} / {}; grid-row: {} / {}\">\n",
+            line(&columns, x),
+            line(&columns, x + w),
+            line(&rows, y),
+            line(&rows, y + h),
+        ));
+
+        buffer.push_str(&format!(
+            "<div class=\"title\">Command: {}</div>\n<pre>",
+            escape(&tile.command.join(" "))
+        ));
+        buffer.push_str(&tile_rows(tile, full).join("\n"));
+        buffer.push_str("</pre>\n</div>\n");
+    }
+
+    buffer.push_str("</div>\n</body>\n</html>\n");
+    buffer
+}
+
+/// Returns the rows of a tile, either the rows it shows or the lines of its whole scrollback.
+///
+/// The rows start with the timestamp of their line when the timestamps are shown in the tile.
+fn tile_rows(tile: &Tile, full: bool) -> Vec<String> {
+    let rule_width = tile.content_width().saturating_sub(1);
+    let gutter = tile.timestamps.width() as usize;
+
+    // Only the first line of a logical line has a timestamp, as in the gutter of the tile
+    let line_row = |index: usize, l: &Line| {
+        let mut row = String::new();
+
+        if gutter > 0 {
+            let timestamp = match tile.is_continuation(index) {
+                true => String::new(),
+                false => tile.format_timestamp(l),
+            };
+
+            row.push_str(&format!(
+                "<span style=\"opacity: 0.5\">{:<width$}</span>",
+                escape(&timestamp),
+                width = gutter
+            ));
+        }
+
+        match l.marker {
+            Some(marker) => row.push_str(&self::marker(marker, rule_width)),
+            None => row.push_str(&line(l)),
+        }
+
+        row
+    };
+
+    // The lines that were wrapped are joined again, as when the scrollback is saved
+    if full {
+        let mut rows: Vec<String> = vec![];
+
+        for (index, l) in tile.stdout.iter() {
+            match rows.last_mut() {
+                Some(row) if tile.is_continuation(index) => row.push_str(&line(&l)),
+                _ => rows.push(line_row(index, &l)),
+            }
+        }
+
+        return rows;
+    }
+
+    (0..=tile.inner_size.1)
+        .map_while(|row| tile.view_row(tile.scroll as usize + row as usize))
+        .map(|row| match row {
+            Row::Line(index) => tile.stdout.get(index).map(|l| line_row(index, &l)),
+            Row::Summary(section) => tile.sections.get(section).map(|s| {
+                format!(
+                    "{:<gutter$}<span style=\"font-weight: bold; {}\">{}</span>",
+                    "",
+                    marker_css(s.marker),
+                    escape(&s.summary()),
+                    gutter = gutter
+                )
+            }),
+        })
+        .map(Option::unwrap_or_default)
+        .collect()
+}
+
+/// Converts a line to HTML, with a span for each run of text with the same style.
+pub fn line(line: &Line) -> String {
+    let mut buffer = String::new();
+    let mut span: Option<(Style, String)> = None;
+
+    for (_, grapheme, style) in line.filled_cells() {
+        match span.as_mut() {
+            Some((current, text)) if *current == style => text.push_str(&grapheme),
+            _ => {
+                push_span(&mut buffer, span.take());
+                span = Some((style, grapheme));
+            }
+        }
+    }
+
+    push_span(&mut buffer, span);
+    buffer
+}
+
+/// Writes a run of text with the same style, in a span unless it has the default style.
+fn push_span(buffer: &mut String, span: Option<(Style, String)>) {
+    if let Some((style, text)) = span {
+        match css(&style) {
+            css if css.is_empty() => buffer.push_str(&escape(&text)),
+            css => buffer.push_str(&format!("<span style=\"{}\">{}</span>", css, escape(&text))),
+        }
+    }
+}
+
+/// Converts a marker to HTML, as a horizontal rule of `w` columns.
+pub fn marker(marker: Marker, w: u16) -> String {
+    format!(
+        "<span style=\"{}\">{}</span>",
+        marker_css(marker),
+        "─".repeat(w as usize)
+    )
+}
+
+/// Returns the CSS of the color of a marker.
+fn marker_css(marker: Marker) -> String {
+    match marker {
+        Marker::Separator => String::new(),
+        Marker::Finish(Some(0)) => format!("color: {};", color(Color::Indexed(2))),
+        Marker::Finish(_) => format!("color: {};", color(Color::Indexed(1))),
+    }
+}
+
+/// Returns the CSS of a style, which is empty for the default style.
+pub fn css(style: &Style) -> String {
+    let mut fg = style.fg.map(color);
+    let mut bg = style.bg.map(color);
+
+    if style.reverse {
+        let swapped = (
+            bg.unwrap_or_else(|| String::from(DEFAULT_COLORS.1)),
+            fg.unwrap_or_else(|| String::from(DEFAULT_COLORS.0)),
+        );
+        (fg, bg) = (Some(swapped.0), Some(swapped.1));
+    }
+
+    let mut css = vec![];
+
+    if style.hidden {
+        css.push(String::from("color: transparent"));
+    } else if let Some(fg) = fg {
+        css.push(format!("color: {}", fg));
+    }
+
+    if let Some(bg) = bg {
+        css.push(format!("background-color: {}", bg));
+    }
+
+    if style.bold {
+        css.push(String::from("font-weight: bold"));
+    }
+
+    if style.dim {
+        css.push(String::from("opacity: 0.5"));
+    }
+
+    if style.italic {
+        css.push(String::from("font-style: italic"));
+    }
+
+    match (style.underline, style.strikethrough) {
+        (true, true) => css.push(String::from("text-decoration: underline line-through")),
+        (true, false) => css.push(String::from("text-decoration: underline")),
+        (false, true) => css.push(String::from("text-decoration: line-through")),
+        (false, false) => (),
+    }
+
+    css.join("; ")
+}
+
+/// Returns the CSS of a color, with the values of the default palette of xterm.
+pub fn color(color: Color) -> String {
+    const STANDARD: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x00, 0x00),
+        (0x00, 0xcd, 0x00),
+        (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee),
+        (0xcd, 0x00, 0xcd),
+        (0x00, 0xcd, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f),
+        (0xff, 0x00, 0x00),
+        (0x00, 0xff, 0x00),
+        (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff),
+        (0xff, 0x00, 0xff),
+        (0x00, 0xff, 0xff),
+        (0xff, 0xff, 0xff),
+    ];
+
+    let level = |i: u8| if i == 0 { 0 } else { 55 + 40 * i };
+
+    let (r, g, b) = match color {
+        Color::Indexed(i) if i < 16 => STANDARD[i as usize],
+
+        // The 6×6×6 color cube
+        Color::Indexed(i) if i < 232 => {
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+
+        // The grayscale ramp
+        Color::Indexed(i) => {
+            let gray = 8 + 10 * (i - 232);
+            (gray, gray, gray)
+        }
+
+        Color::Rgb(r, g, b) => (r, g, b),
+    };
+
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Escapes the characters that have a meaning in HTML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans() {
+        let l = Line {
+            content: String::from("\x1b[31mred\x1b[0m plain \x1b[1;4m<b> & \"u\"\x1b[0m\n"),
+            ..Line::default()
+        };
+
+        assert_eq!(
+            line(&l),
+            "<span style=\"color: #cd0000\">red</span> plain <span style=\"font-weight: bold; \
+             text-decoration: underline\">&lt;b&gt; &amp; &quot;u&quot;</span>"
+        );
+    }
+
+    #[test]
+    fn reverse() {
+        let mut style = Style {
+            reverse: true,
+            ..Style::default()
+        };
+
+        // The default colors are swapped too
+        assert_eq!(css(&style), "color: #000000; background-color: #e5e5e5");
+
+        style.fg = Some(Color::Indexed(1));
+        assert_eq!(css(&style), "color: #000000; background-color: #cd0000");
+
+        style.bg = Some(Color::Rgb(1, 2, 3));
+        assert_eq!(css(&style), "color: #010203; background-color: #cd0000");
+    }
+
+    #[test]
+    fn palette() {
+        assert_eq!(color(Color::Indexed(0)), "#000000");
+        assert_eq!(color(Color::Indexed(12)), "#5c5cff");
+
+        // The 6×6×6 color cube
+        assert_eq!(color(Color::Indexed(16)), "#000000");
+        assert_eq!(color(Color::Indexed(17)), "#00005f");
+        assert_eq!(color(Color::Indexed(196)), "#ff0000");
+        assert_eq!(color(Color::Indexed(231)), "#ffffff");
+
+        // The grayscale ramp
+        assert_eq!(color(Color::Indexed(232)), "#080808");
+        assert_eq!(color(Color::Indexed(255)), "#eeeeee");
+
+        assert_eq!(color(Color::Rgb(0xab, 0xcd, 0xef)), "#abcdef");
+    }
+}
@@ -0,0 +1,138 @@
+//! This module contains the reading of the keys and of the mouse events of the terminal.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use termion::event::{self, Event, Key};
+
+use crate::utils;
+
+/// The input of the terminal, which can be left to another program, e.g. a pager.
+///
+/// The input is read without any buffer, so that no event stays hidden from `poll` once it was
+/// read from the terminal, and nothing that is meant for the other program is read.
+#[derive(Default)]
+pub struct Input {
+    /// Whether the input is left to another program.
+    paused: AtomicBool,
+
+    /// The lock held while waiting for the input and reading it.
+    reading: Mutex<()>,
+}
+
+impl Input {
+    /// Creates the input of the terminal.
+    pub fn new() -> Input {
+        Input::default()
+    }
+
+    /// Stops reading the input, and waits until the read in progress, if any, is over.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        drop(self.reading.lock());
+    }
+
+    /// Reads the input again after it was paused.
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Waits for at most `timeout` for events, and returns them, or `None` at the end of the
+    /// input.
+    pub fn read(&self, timeout: Duration) -> io::Result<Option<Vec<Event>>> {
+        let reading = self.reading.lock().unwrap_or_else(|e| e.into_inner());
+
+        if self.paused.load(Ordering::SeqCst) {
+            drop(reading);
+            thread::sleep(timeout);
+            return Ok(Some(vec![]));
+        }
+
+        if !utils::wait_for_input(timeout) {
+            return Ok(Some(vec![]));
+        }
+
+        let mut buffer = [0u8; 1024];
+
+        // SAFETY: `read` writes at most `buffer.len()` bytes in the buffer, which is valid
+        let read = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+
+        match read {
+            0 => Ok(None),
+            n if n > 0 => Ok(Some(parse(&buffer[..n as usize]))),
+            _ => match io::Error::last_os_error() {
+                e if e.kind() == io::ErrorKind::Interrupted => Ok(Some(vec![])),
+                e => Err(e),
+            },
+        }
+    }
+}
+
+/// Parses the events in bytes that were read at once.
+///
+/// An escape alone is the escape key, while an escape followed by other bytes starts a sequence,
+/// as the terminal writes the sequences at once.
+pub fn parse(bytes: &[u8]) -> Vec<Event> {
+    let mut events = vec![];
+    let mut iter = bytes.iter().map(|b| Ok(*b));
+
+    while let Some(Ok(byte)) = iter.next() {
+        let event = match byte {
+            b'\x1b' if iter.len() == 0 => Ok(Event::Key(Key::Esc)),
+            byte => event::parse_event(byte, &mut iter),
+        };
+
+        // The sequences that can't be parsed are dropped
+        if let Ok(event) = event {
+            events.push(event);
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use termion::event::{MouseButton, MouseEvent};
+
+    use super::*;
+
+    #[test]
+    fn several_events() {
+        assert_eq!(
+            parse(b"jk\x1b[A\x1b"),
+            vec![
+                Event::Key(Key::Char('j')),
+                Event::Key(Key::Char('k')),
+                Event::Key(Key::Up),
+                Event::Key(Key::Esc),
+            ]
+        );
+    }
+
+    #[test]
+    fn wheel_burst() {
+        let event = Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 3, 4));
+        assert_eq!(
+            parse(b"\x1b[<65;3;4M\x1b[<65;3;4M"),
+            vec![event.clone(), event]
+        );
+    }
+
+    #[test]
+    fn multi_byte_characters() {
+        assert_eq!(
+            parse("é€".as_bytes()),
+            vec![Event::Key(Key::Char('é')), Event::Key(Key::Char('€'))]
+        );
+    }
+}
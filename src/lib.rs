@@ -1,24 +1,79 @@
-use std::io::{self, stdin, stdout, Write};
+use std::fs;
+use std::io::{self, stdout, IsTerminal, Stdout, Write};
+use std::process::Command;
 use std::sync::mpsc::channel;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use std::{env, thread};
 
 use termion::event::{Event, Key, MouseButton, MouseEvent};
-use termion::input::{MouseTerminal, TermRead};
-use termion::raw::IntoRawMode;
-use termion::screen::IntoAlternateScreen;
+use termion::input::MouseTerminal;
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::{self, AlternateScreen, IntoAlternateScreen};
 use termion::terminal_size;
-use termion::{clear, cursor};
-
-use tile::{Tile, TileBuilder};
-
+use termion::{clear, color, cursor, style};
+
+use regex::Regex;
+
+use input::Input;
+use options::{Notify, Options};
+use record::Recorder;
+use search::{Filter, Search};
+use tile::{LinkMode, Tile, TileBuilder};
+
+pub mod ansi;
+pub mod encoding;
+pub mod html;
+pub mod input;
+pub mod line;
+pub mod log;
+pub mod options;
+pub mod plain;
+pub mod record;
+pub mod scrollback;
+pub mod search;
+pub mod section;
+pub mod selection;
 pub mod tile;
 pub mod utils;
 
 const DELAY: Duration = Duration::from_millis(20);
 
+/// A terminal that can be handed over to another program, e.g. a pager, and taken back.
+pub trait Terminal: Write {
+    /// Gives the terminal back in the state it was before tileview started.
+    fn suspend(&mut self) -> io::Result<()>;
+
+    /// Takes the terminal again after it was suspended.
+    fn resume(&mut self) -> io::Result<()>;
+}
+
+impl Terminal for MouseTerminal<AlternateScreen<RawTerminal<Stdout>>> {
+    fn suspend(&mut self) -> io::Result<()> {
+        write!(
+            self,
+            "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l{}{}",
+            screen::ToMainScreen,
+            cursor::Show
+        )?;
+        self.flush()?;
+        self.suspend_raw_mode()
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        self.activate_raw_mode()?;
+        write!(
+            self,
+            "{}\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h{}",
+            screen::ToAlternateScreen,
+            cursor::Hide
+        )?;
+        self.flush()
+    }
+}
+
 /// Multiple applications running in a single terminal.
-struct Multiview<W: Write> {
+struct Multiview<W: Terminal> {
     /// The stdout on which the multiview will be rendererd.
     pub stdout: W,
 
@@ -28,6 +83,9 @@ struct Multiview<W: Write> {
     /// The coordinates of the selected tiles.
     pub selected: (u16, u16),
 
+    /// The options of the multiview.
+    pub options: Options,
+
     /// Whether we need to refresh the UI.
     pub refresh_ui: bool,
 
@@ -36,27 +94,80 @@ struct Multiview<W: Write> {
 
     /// Last time when the rendering was performed.
     pub last_render: Instant,
+
+    /// The input that is asked to the user, if any.
+    pub prompt: Option<Prompt>,
+
+    /// How the scroll of the tiles that are linked is synchronized.
+    pub link_mode: LinkMode,
+
+    /// The input of the terminal, which is left to another program, e.g. a pager, while it runs.
+    pub input: Arc<Input>,
+
+    /// The recording of the screen, if it is recorded.
+    pub recorder: Option<Recorder>,
+
+    /// Whether the user asked to exit.
+    pub exiting: bool,
 }
 
-impl<W: Write> Multiview<W> {
+/// What the user is asked to type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PromptKind {
+    /// A regex to search in the selected tile.
+    Search,
+
+    /// A regex that the lines of the selected tile must match to be shown.
+    Filter,
+
+    /// The number of the line of output to scroll to.
+    GoTo,
+}
+
+/// An input asked to the user, shown in the title of the selected tile.
+pub struct Prompt {
+    /// What the user is asked to type.
+    pub kind: PromptKind,
+
+    /// What the user typed.
+    pub input: String,
+
+    /// Whether the input is invalid.
+    pub error: bool,
+
+    /// The scroll of the selected tile when the prompt was opened.
+    pub origin: (isize, bool),
+}
+
+impl<W: Terminal> Multiview<W> {
     /// Creates a new multiview.
-    pub fn new(stdout: W, tiles: Vec<Vec<Tile>>) -> io::Result<Multiview<W>> {
+    pub fn new(stdout: W, tiles: Vec<Vec<Tile>>, options: Options) -> io::Result<Multiview<W>> {
+        let recorder = match options.record.as_ref() {
+            Some(path) => Some(Recorder::create(path, terminal_size()?, "tileview")?),
+            None => None,
+        };
+
         let mut multiview = Multiview {
             stdout,
             tiles,
             selected: (0, 0),
+            options,
             refresh_ui: true,
             refresh_tiles: false,
             last_render: Instant::now(),
+            prompt: None,
+            link_mode: LinkMode::default(),
+            input: Arc::new(Input::new()),
+            recorder,
+            exiting: false,
         };
 
-        write!(
-            multiview.stdout,
-            "{}{}{}",
-            clear::All,
-            cursor::Hide,
-            cursor::Goto(1, 1)
-        )?;
+        let init = format!("{}{}{}", clear::All, cursor::Hide, cursor::Goto(1, 1));
+        write!(multiview.stdout, "{}", init)?;
+
+        if let Some(recorder) = multiview.recorder.as_ref() {
+            recorder.output(&init);
+        }
 
         multiview.stdout.flush()?;
 
@@ -73,25 +184,37 @@ impl<W: Write> Multiview<W> {
         &mut self.tiles[i as usize][j as usize]
     }
 
-    /// Sets the selected tile from (x, y) coordinates.
-    pub fn select_tile(&mut self, (x, y): (u16, u16)) {
+    /// Returns the coordinates of the tile under a point of the screen.
+    pub fn tile_at(&self, (x, y): (u16, u16)) -> Option<(u16, u16)> {
         // Ugly but working
         for (i, row) in self.tiles.iter().enumerate() {
             for (j, tile) in row.iter().enumerate() {
-                if tile.outer_position.0 <= x && x < tile.outer_position.0 + tile.outer_size.0 {
-                    if tile.outer_position.1 <= y && y < tile.outer_position.1 + tile.outer_size.1 {
-                        self.selected = (i as u16, j as u16);
-                    }
+                if tile.outer_position.0 <= x
+                    && x < tile.outer_position.0 + tile.outer_size.0
+                    && tile.outer_position.1 <= y
+                    && y < tile.outer_position.1 + tile.outer_size.1
+                {
+                    return Some((i as u16, j as u16));
                 }
             }
         }
+
+        None
+    }
+
+    /// Sets the selected tile from (x, y) coordinates.
+    pub fn select_tile(&mut self, (x, y): (u16, u16)) {
+        if let Some(coords) = self.tile_at((x, y)) {
+            self.selected = coords;
+        }
+        self.tile_mut(self.selected).clear_notifications();
         self.refresh_ui = true;
     }
 
     /// Renders the border and the title of a tile.
     pub fn render_tile_border(&self, (i, j): (u16, u16)) -> String {
         let tile = &self.tile((i, j));
-        tile.render_border(self.selected == ((i, j)))
+        tile.render_border(self.selected == (i, j))
     }
 
     /// Renders the (x, y) tile.
@@ -100,6 +223,45 @@ impl<W: Write> Multiview<W> {
         tile.render_content(self.selected == (i, j))
     }
 
+    /// Renders the prompt in the title of the selected tile.
+    pub fn render_prompt(&self) -> Option<String> {
+        let prompt = self.prompt.as_ref()?;
+        let tile = self.tile(self.selected);
+        let (x, y) = tile.outer_position;
+        let (w, _) = tile.outer_size;
+
+        let prefix = match prompt.kind {
+            PromptKind::Search => "/",
+            PromptKind::Filter => "&",
+            PromptKind::GoTo => ":",
+        };
+
+        let error = if prompt.error { " (invalid)" } else { "" };
+
+        // Only show the end of the input if it is too long
+        let max_len = (w as usize).saturating_sub(6 + prefix.len() + error.len());
+        let skip = prompt.input.chars().count().saturating_sub(max_len);
+        let input = prompt.input.chars().skip(skip).collect::<String>();
+
+        let line = format!(" {}{}", prefix, input);
+        let padding =
+            (w as usize - 2).saturating_sub(utils::text_width(&line) as usize + 1 + error.len());
+
+        Some(format!(
+            "{}{}{}{}{} {}{}{}{}{}",
+            style::Reset,
+            cursor::Goto(x + 1, y + 1),
+            style::Bold,
+            line,
+            style::Invert,
+            style::Reset,
+            color::Red.fg_str(),
+            error,
+            style::Reset,
+            " ".repeat(padding),
+        ))
+    }
+
     /// Renders all the tiles of the multiview.
     pub fn render(&mut self, force: bool) -> io::Result<()> {
         if !self.refresh_tiles {
@@ -120,51 +282,168 @@ impl<W: Write> Multiview<W> {
             vec![]
         };
 
+        // Borders are always rendered since their title shows the status of the tiles
         for i in 0..self.tiles.len() {
             for j in 0..self.tiles[i].len() {
-                if self.refresh_ui {
-                    buffer.push(self.render_tile_border((i as u16, j as u16)));
-                }
+                buffer.push(self.render_tile_border((i as u16, j as u16)));
                 buffer.push(self.render_tile_content((i as u16, j as u16)));
             }
         }
 
+        if let Some(prompt) = self.render_prompt() {
+            buffer.push(prompt);
+        }
+
         self.refresh_ui = false;
         self.refresh_tiles = false;
-        write!(self.stdout, "{}", buffer.join(""))?;
+        let frame = buffer.join("");
+        write!(self.stdout, "{}", frame)?;
         self.stdout.flush()?;
 
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.output(&frame);
+        }
+
         Ok(())
     }
 
-    /// Scrolls down the current selected tile.
-    pub fn scroll_down(&mut self, step: isize) {
+    /// Scrolls the tile under the pointer when the wheel is used, a positive step scrolling down.
+    pub fn wheel(&mut self, (x, y): (u16, u16), step: isize) {
+        let coords = match self.tile_at((x, y)) {
+            Some(coords) => coords,
+            None => return,
+        };
+
+        if self.options.focus_follows_mouse && coords != self.selected {
+            self.select_tile((x, y));
+        }
+
+        if step > 0 {
+            self.scroll_linked(coords, |tile| tile.scroll_down(step));
+        } else {
+            self.scroll_linked(coords, |tile| tile.scroll_up(-step));
+        }
+    }
+
+    /// Scrolls a tile, and the tiles that are linked to it.
+    pub fn scroll_linked<F: Fn(&mut Tile)>(&mut self, source: (u16, u16), scroll: F) {
+        scroll(self.tile_mut(source));
+
+        let mode = match self.tile(source).link {
+            Some(mode) => mode,
+            None => return,
+        };
+
+        let time = self.tile(source).top_timestamp();
+
+        for (i, row) in self.tiles.iter_mut().enumerate() {
+            for (j, tile) in row.iter_mut().enumerate() {
+                if (i as u16, j as u16) == source || tile.link.is_none() {
+                    continue;
+                }
+
+                match (mode, time) {
+                    (LinkMode::Time, Some(time)) => tile.scroll_to_time(time),
+                    _ => scroll(tile),
+                }
+            }
+        }
+    }
+
+    /// Links the selected tile to the other linked tiles, or unlinks it.
+    pub fn toggle_link(&mut self) {
+        let mode = self.link_mode;
         let tile = self.tile_mut(self.selected);
-        tile.scroll_down(step);
+        tile.link = match tile.link {
+            Some(_) => None,
+            None => Some(mode),
+        };
     }
 
-    /// Scrolls up the current selected tile.
+    /// Switches between synchronizing the linked tiles by offset and by time.
+    pub fn cycle_link_mode(&mut self) {
+        self.link_mode = match self.link_mode {
+            LinkMode::Offset => LinkMode::Time,
+            LinkMode::Time => LinkMode::Offset,
+        };
+
+        for tile in self.tiles.iter_mut().flatten() {
+            if tile.link.is_some() {
+                tile.link = Some(self.link_mode);
+            }
+        }
+    }
+
+    /// Scrolls down the current selected tile, and the tiles linked to it.
+    pub fn scroll_down(&mut self, step: isize) {
+        self.scroll_linked(self.selected, |tile| tile.scroll_down(step));
+    }
+
+    /// Scrolls up the current selected tile, and the tiles linked to it.
     pub fn scroll_up(&mut self, step: isize) {
-        let tile = self.tile_mut(self.selected);
-        tile.scroll_up(step);
+        self.scroll_linked(self.selected, |tile| tile.scroll_up(step));
     }
 
-    /// Scrolls down to the bottom of the current selected tile.
+    /// Scrolls down to the bottom of the current selected tile, and the tiles linked to it.
     pub fn scroll_full_down(&mut self) {
-        let tile = self.tile_mut(self.selected);
-        tile.scroll_full_down();
+        self.scroll_linked(self.selected, |tile| tile.scroll_full_down());
     }
 
-    /// Scrolls up to the top the current selected tile.
+    /// Scrolls up to the top the current selected tile, and the tiles linked to it.
     pub fn scroll_full_up(&mut self) {
-        let tile = self.tile_mut(self.selected);
-        tile.scroll_full_up();
+        self.scroll_linked(self.selected, |tile| tile.scroll_full_up());
     }
 
     /// Push a string into a tile's stdout.
     pub fn push_stdout(&mut self, (i, j): (u16, u16), content: String) {
+        let selected = self.selected == (i, j);
         let tile = self.tile_mut((i, j));
+        let (bell, activity) = (tile.bell, tile.activity);
         tile.push_stdout(content);
+
+        if selected {
+            tile.clear_notifications();
+            return;
+        }
+
+        let new_bell = !bell && tile.bell;
+        let new_activity = !activity && tile.activity;
+
+        if new_bell || new_activity {
+            let message = format!(
+                "{} {}",
+                tile.command.join(" "),
+                if new_bell {
+                    "rang the bell"
+                } else {
+                    "has new output"
+                }
+            );
+            self.notify(&message);
+            self.refresh_ui = true;
+        }
+    }
+
+    /// Forwards a notification of a background tile to the terminal.
+    pub fn notify(&mut self, message: &str) {
+        // The message must not end the escape sequence
+        let message = message
+            .chars()
+            .filter(|c| !c.is_control() && *c != ';')
+            .collect::<String>();
+
+        match self.options.notify {
+            Notify::None => (),
+            Notify::Bell => {
+                write!(self.stdout, "\x07").ok();
+            }
+            Notify::Osc9 => {
+                write!(self.stdout, "\x1b]9;tileview: {}\x07", message).ok();
+            }
+            Notify::Osc777 => {
+                write!(self.stdout, "\x1b]777;notify;tileview;{}\x07", message).ok();
+            }
+        }
     }
 
     /// Push a string into a tile's stderr.
@@ -218,13 +497,18 @@ impl<W: Write> Multiview<W> {
     }
 
     /// Adds a finish line to the specified tile.
-    pub fn add_finish_line(&mut self, coords: (u16, u16), success: bool) {
+    pub fn add_finish_line(&mut self, coords: (u16, u16), run: usize, code: Option<i32>) {
         let tile = self.tile_mut(coords);
-        tile.add_finish_line(success);
+
+        // The runs that were restarted were finished when they were killed
+        if run == tile.run {
+            tile.add_finish_line(code);
+        }
     }
 
     /// Exits.
     pub fn exit(&mut self) {
+        self.exiting = true;
         write!(self.stdout, "{}", cursor::Show).ok();
 
         for row in &mut self.tiles {
@@ -234,6 +518,400 @@ impl<W: Write> Multiview<W> {
         }
     }
 
+    /// Returns the message corresponding to a key pressed by the user.
+    pub fn key_to_msg(&self, key: Key) -> Option<Msg> {
+        if self.prompt.is_some() {
+            return Some(Msg::PromptKey(key));
+        }
+
+        if self.tile(self.selected).selection.is_some() {
+            return Some(Msg::CopyKey(key));
+        }
+
+        let page = self.tile(self.selected).page_size();
+        let half_width = std::cmp::max(1, self.tile(self.selected).content_width() / 2) as isize;
+
+        let msg = match key {
+            Key::Esc | Key::Ctrl('c') | Key::Char('q') => Msg::Exit,
+            Key::Char('r') => Msg::Restart,
+            Key::Char('R') => Msg::RestartAll,
+            Key::Char('k') => Msg::Kill,
+            Key::Char('K') => Msg::KillAll,
+            Key::Char('l') => Msg::AddLine,
+            Key::Char('L') => Msg::AddLineAll,
+            Key::Char('/') => Msg::OpenPrompt(PromptKind::Search),
+            Key::Char('&') => Msg::OpenPrompt(PromptKind::Filter),
+            Key::Char('n') => Msg::SearchNext,
+            Key::Char('N') => Msg::SearchPrevious,
+            Key::Char('v') => Msg::CopyMode,
+            Key::Char('t') => Msg::CycleTimestamps,
+            Key::Char('s') => Msg::ToggleLink,
+            Key::Char('S') => Msg::CycleLinkMode,
+            Key::Char(']') => Msg::NextCounted,
+            Key::Char('[') => Msg::PreviousCounted,
+            Key::Char('w') => Msg::CycleWrap,
+            Key::Left => Msg::ScrollHorizontally(-half_width),
+            Key::Right => Msg::ScrollHorizontally(half_width),
+            Key::Char('e') => Msg::SaveScrollback(false),
+            Key::Char('E') => Msg::SaveScrollback(true),
+            Key::Char('o') => Msg::OpenScrollback,
+            Key::Char('h') => Msg::ExportHtml(false),
+            Key::Char('H') => Msg::ExportHtml(true),
+            Key::Char('z') => Msg::ToggleSection,
+            Key::Char('Z') => Msg::ToggleAllSections,
+            Key::Char(':') => Msg::OpenPrompt(PromptKind::GoTo),
+            Key::Down => Msg::ScrollDown(1),
+            Key::Up => Msg::ScrollUp(1),
+            Key::PageDown | Key::Char(' ') => Msg::ScrollDown(page),
+            Key::PageUp | Key::Char('b') => Msg::ScrollUp(page),
+            Key::Ctrl('d') => Msg::ScrollDown(page / 2),
+            Key::Ctrl('u') => Msg::ScrollUp(page / 2),
+            Key::End | Key::Char('G') => Msg::ScrollFullDown,
+            Key::Home | Key::Char('g') => Msg::ScrollFullUp,
+            _ => return None,
+        };
+
+        Some(msg)
+    }
+
+    /// Opens a prompt in the title of the selected tile.
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        let tile = self.tile(self.selected);
+
+        // The current filter is shown so that the user can edit it
+        let input = match (kind, &tile.filter) {
+            (PromptKind::Filter, Some(filter)) => filter.regex.as_str().to_string(),
+            _ => String::new(),
+        };
+
+        self.prompt = Some(Prompt {
+            kind,
+            input,
+            error: false,
+            origin: (tile.scroll, tile.sticky),
+        });
+    }
+
+    /// Treats a key pressed while the prompt is open.
+    pub fn prompt_key(&mut self, key: Key) {
+        let mut prompt = match self.prompt.take() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+
+        match key {
+            Key::Char('\n') => return self.submit_prompt(prompt),
+
+            Key::Esc | Key::Ctrl('c') => return self.cancel_prompt(prompt),
+
+            Key::Backspace => {
+                prompt.input.pop();
+            }
+
+            Key::Char(c) => prompt.input.push(c),
+
+            _ => (),
+        }
+
+        self.update_prompt(&mut prompt);
+        self.prompt = Some(prompt);
+    }
+
+    /// Applies what the user is typing in the prompt.
+    pub fn update_prompt(&mut self, prompt: &mut Prompt) {
+        let tile = self.tile_mut(self.selected);
+
+        match prompt.kind {
+            PromptKind::Search => {
+                // The search restarts from where the user was when the prompt was opened
+                (tile.scroll, tile.sticky) = prompt.origin;
+
+                if prompt.input.is_empty() {
+                    tile.set_search(None);
+                    prompt.error = false;
+                    return;
+                }
+
+                match Regex::new(&prompt.input) {
+                    Ok(regex) => {
+                        tile.set_search(Some(Search::new(regex)));
+                        tile.search_next(true);
+                        prompt.error = false;
+                    }
+                    Err(_) => prompt.error = true,
+                }
+            }
+
+            // Filtering is only applied once the user validates, since it can hide everything
+            // while the regex is being typed
+            PromptKind::Filter => {
+                prompt.error = Regex::new(&prompt.input).is_err();
+            }
+
+            PromptKind::GoTo => {
+                prompt.error = prompt.input.parse::<usize>().is_err() && !prompt.input.is_empty();
+            }
+        }
+    }
+
+    /// Validates the input of the prompt.
+    pub fn submit_prompt(&mut self, prompt: Prompt) {
+        match prompt.kind {
+            // The search was already applied while the user was typing
+            PromptKind::Search => (),
+
+            PromptKind::Filter => {
+                let tile = self.tile_mut(self.selected);
+
+                if prompt.input.is_empty() {
+                    tile.set_filter(None);
+                    return;
+                }
+
+                match Regex::new(&prompt.input) {
+                    Ok(regex) => tile.set_filter(Some(Filter::new(regex))),
+
+                    // Let the user fix the regex
+                    Err(_) => self.prompt = Some(prompt),
+                }
+            }
+
+            PromptKind::GoTo => {
+                let tile = self.tile_mut(self.selected);
+
+                match prompt.input.parse::<usize>() {
+                    Ok(line) => tile.go_to_line(line),
+                    Err(_) if prompt.input.is_empty() => (),
+                    Err(_) => self.prompt = Some(prompt),
+                }
+            }
+        }
+    }
+
+    /// Closes the prompt without applying its input.
+    pub fn cancel_prompt(&mut self, prompt: Prompt) {
+        let tile = self.tile_mut(self.selected);
+
+        match prompt.kind {
+            PromptKind::Search => {
+                tile.set_search(None);
+                (tile.scroll, tile.sticky) = prompt.origin;
+            }
+
+            PromptKind::Filter | PromptKind::GoTo => (),
+        }
+    }
+
+    /// Cycles between the ways of showing the timestamps of the selected tile.
+    pub fn cycle_timestamps(&mut self) {
+        let tile = self.tile_mut(self.selected);
+        tile.cycle_timestamps();
+    }
+
+    /// Scrolls the selected tile to its next or previous error or warning.
+    pub fn jump_to_counted(&mut self, forward: bool) {
+        let tile = self.tile_mut(self.selected);
+        tile.jump_to_counted(forward);
+    }
+
+    /// Cycles between the ways of showing the wide lines of the selected tile.
+    pub fn cycle_wrap(&mut self) {
+        self.tile_mut(self.selected).cycle_wrap();
+    }
+
+    /// Scrolls the selected tile horizontally, to the left if `step` is negative.
+    pub fn scroll_horizontally(&mut self, step: isize) {
+        self.tile_mut(self.selected).scroll_horizontally(step);
+    }
+
+    /// Folds or unfolds the section at the top of the selected tile.
+    pub fn toggle_section(&mut self) {
+        self.tile_mut(self.selected).toggle_top_section();
+    }
+
+    /// Folds or unfolds all the sections of the selected tile.
+    pub fn toggle_all_sections(&mut self) {
+        self.tile_mut(self.selected).toggle_all_sections();
+    }
+
+    /// Saves the scrollback of the selected tile to a file, as plain text or with its escape
+    /// sequences.
+    pub fn save_scrollback(&mut self, ansi: bool) {
+        let tile = self.tile_mut(self.selected);
+        tile.notice = Some(match tile.save_scrollback(ansi) {
+            Ok(path) => format!("saved to {}", path.display()),
+            Err(e) => format!("couldn't save: {}", e),
+        });
+    }
+
+    /// Saves the scrollback of the selected tile to a file, and opens it in `$PAGER` or
+    /// `$EDITOR` while the UI is suspended.
+    pub fn open_scrollback(&mut self) -> io::Result<()> {
+        let path = match self.tile(self.selected).save_scrollback(false) {
+            Ok(path) => path,
+            Err(e) => {
+                self.tile_mut(self.selected).notice = Some(format!("couldn't save: {}", e));
+                return Ok(());
+            }
+        };
+
+        let program = env::var("PAGER")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| String::from("less"));
+
+        // The program can have arguments, e.g. `less -R`
+        self.input.pause();
+        self.stdout.suspend()?;
+
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", program))
+            .arg("sh")
+            .arg(&path)
+            .status();
+
+        self.stdout.resume()?;
+        self.input.resume();
+        self.refresh_ui = true;
+
+        self.tile_mut(self.selected).notice = Some(match status {
+            Ok(_) => format!("saved to {}", path.display()),
+            Err(e) => format!("couldn't run {}: {}", program, e),
+        });
+
+        Ok(())
+    }
+
+    /// Exports the tiles to an HTML file named after the current time, with the rows they show
+    /// or with their whole scrollback if `full` is true.
+    ///
+    /// The file is written in the log directory if any, or in the current directory.
+    pub fn export_html(&mut self, full: bool) {
+        let tiles = self.tiles.iter().flatten().collect::<Vec<_>>();
+        let document = html::document(&tiles, full);
+
+        let dir = self.options.tile.log_dir.clone().unwrap_or_default();
+        let path = dir.join(format!(
+            "tileview-{}.html",
+            utils::format_file_time(SystemTime::now())
+        ));
+
+        let result = if dir.as_os_str().is_empty() {
+            Ok(())
+        } else {
+            fs::create_dir_all(&dir)
+        };
+
+        self.tile_mut(self.selected).notice =
+            Some(match result.and_then(|_| fs::write(&path, document)) {
+                Ok(()) => format!("exported to {}", path.display()),
+                Err(e) => format!("couldn't export: {}", e),
+            });
+    }
+
+    /// Removes the notice of the selected tile, once the user pressed a key after seeing it.
+    pub fn clear_notice(&mut self) {
+        self.tile_mut(self.selected).notice = None;
+    }
+
+    /// Enters copy mode in the selected tile.
+    pub fn copy_mode(&mut self) {
+        self.tile_mut(self.selected).enter_copy_mode();
+    }
+
+    /// Treats a key pressed while the selected tile is in copy mode.
+    pub fn copy_key(&mut self, key: Key) {
+        let tile = self.tile_mut(self.selected);
+
+        match key {
+            Key::Esc | Key::Ctrl('c') | Key::Char('q') => tile.exit_copy_mode(),
+            Key::Left | Key::Char('h') => tile.move_cursor(0, -1),
+            Key::Right | Key::Char('l') => tile.move_cursor(0, 1),
+            Key::Up | Key::Char('k') => tile.move_cursor(-1, 0),
+            Key::Down | Key::Char('j') => tile.move_cursor(1, 0),
+            Key::PageUp => tile.move_cursor(-tile.page_size(), 0),
+            Key::PageDown => tile.move_cursor(tile.page_size(), 0),
+            Key::Home | Key::Char('0') => tile.move_cursor_to_edge(false),
+            Key::End | Key::Char('$') => tile.move_cursor_to_edge(true),
+            Key::Char('v') | Key::Char(' ') => tile.toggle_selection(),
+            Key::Char('y') | Key::Char('\n') => self.copy_selection(),
+            _ => (),
+        }
+    }
+
+    /// Copies the text selected in the selected tile to the clipboard, and leaves copy mode.
+    pub fn copy_selection(&mut self) {
+        let tile = self.tile_mut(self.selected);
+        let text = tile.selected_text();
+        tile.exit_copy_mode();
+
+        // OSC 52 asks the terminal to set the clipboard, which also works over SSH
+        if let Some(text) = text {
+            write!(
+                self.stdout,
+                "\x1b]52;c;{}\x07",
+                utils::base64(text.as_bytes())
+            )
+            .ok();
+        }
+    }
+
+    /// Selects the tile under the pointer, and starts selecting text if the pointer is on its
+    /// content, or unfolds the section whose summary is under the pointer.
+    pub fn click(&mut self, (x, y): (u16, u16)) {
+        self.select_tile((x, y));
+
+        let tile = self.tile_mut(self.selected);
+        if tile.is_on_scrollbar((x, y)) {
+            tile.click_scrollbar((x, y));
+        } else if let Some(section) = tile.summary_at((x, y)) {
+            tile.toggle_section(section);
+        } else if tile.is_on_content((x, y)) {
+            tile.start_drag((x, y));
+        }
+    }
+
+    /// Moves the scrollbar or extends the selection of the selected tile while the mouse is
+    /// dragged.
+    pub fn drag(&mut self, (x, y): (u16, u16)) {
+        let tile = self.tile_mut(self.selected);
+        if tile.scrollbar_grab.is_some() {
+            tile.drag_scrollbar((x, y));
+        } else if tile.selection.map(|s| s.mouse).unwrap_or(false) {
+            tile.drag((x, y));
+        }
+    }
+
+    /// Releases the scrollbar, or copies the text selected with the mouse, when the button is
+    /// released.
+    pub fn release(&mut self, (x, y): (u16, u16)) {
+        let tile = self.tile_mut(self.selected);
+
+        if tile.scrollbar_grab.take().is_some() {
+            return;
+        }
+
+        let selection = match tile.selection {
+            Some(selection) if selection.mouse => selection,
+            _ => return,
+        };
+
+        tile.drag((x, y));
+
+        // A click without moving doesn't select anything
+        if tile.selection.map(|s| s.cursor) == selection.anchor {
+            tile.exit_copy_mode();
+        } else {
+            self.copy_selection();
+        }
+    }
+
+    /// Jumps to the next match of the search in the selected tile.
+    pub fn search_next(&mut self, forward: bool) {
+        let tile = self.tile_mut(self.selected);
+        tile.search_next(forward);
+    }
+
     /// Treats a message.
     pub fn manage_msg(&mut self, msg: Msg) -> io::Result<()> {
         self.refresh_tiles = true;
@@ -241,7 +919,10 @@ impl<W: Write> Multiview<W> {
         match msg {
             Msg::Stdout(coords, line) => self.push_stdout(coords, line),
             Msg::Stderr(coords, line) => self.push_stderr(coords, line),
-            Msg::Click(x, y) => self.select_tile((x, y)),
+            Msg::Click(x, y) => self.click((x, y)),
+            Msg::Wheel(x, y, step) => self.wheel((x, y), step),
+            Msg::Drag(x, y) => self.drag((x, y)),
+            Msg::Release(x, y) => self.release((x, y)),
             Msg::Restart => self.restart(),
             Msg::RestartAll => self.restart_all(),
             Msg::Kill => self.kill(),
@@ -252,7 +933,31 @@ impl<W: Write> Multiview<W> {
             Msg::ScrollFullUp => self.scroll_full_up(),
             Msg::AddLine => self.add_line(),
             Msg::AddLineAll => self.add_line_all(),
-            Msg::AddFinishLine(coords, success) => self.add_finish_line(coords, success),
+            Msg::AddFinishLine(coords, run, code) => self.add_finish_line(coords, run, code),
+            Msg::OpenPrompt(kind) => self.open_prompt(kind),
+            Msg::PromptKey(key) => self.prompt_key(key),
+            Msg::SearchNext => self.search_next(true),
+            Msg::SearchPrevious => self.search_next(false),
+            Msg::CopyMode => self.copy_mode(),
+            Msg::CycleTimestamps => self.cycle_timestamps(),
+            Msg::ToggleLink => self.toggle_link(),
+            Msg::CycleLinkMode => self.cycle_link_mode(),
+            Msg::NextCounted => self.jump_to_counted(true),
+            Msg::PreviousCounted => self.jump_to_counted(false),
+            Msg::CycleWrap => self.cycle_wrap(),
+            Msg::ScrollHorizontally(step) => self.scroll_horizontally(step),
+            Msg::SaveScrollback(ansi) => self.save_scrollback(ansi),
+            Msg::OpenScrollback => self.open_scrollback()?,
+            Msg::ExportHtml(full) => self.export_html(full),
+            Msg::ToggleSection => self.toggle_section(),
+            Msg::ToggleAllSections => self.toggle_all_sections(),
+            Msg::CopyKey(key) => self.copy_key(key),
+            Msg::Key(key) => {
+                self.clear_notice();
+                if let Some(msg) = self.key_to_msg(key) {
+                    self.manage_msg(msg)?;
+                }
+            }
             Msg::Exit => self.exit(),
         }
 
@@ -260,7 +965,7 @@ impl<W: Write> Multiview<W> {
     }
 }
 
-impl<W: Write> Drop for Multiview<W> {
+impl<W: Terminal> Drop for Multiview<W> {
     fn drop(&mut self) {
         self.exit();
     }
@@ -275,9 +980,21 @@ pub enum Msg {
     /// An stderr line arrived.
     Stderr((u16, u16), String),
 
+    /// A key was pressed.
+    Key(Key),
+
     /// A click occured.
     Click(u16, u16),
 
+    /// The wheel was used, a positive step scrolling down.
+    Wheel(u16, u16, isize),
+
+    /// The mouse moved while its button was pressed.
+    Drag(u16, u16),
+
+    /// The button of the mouse was released.
+    Release(u16, u16),
+
     /// Restarts the selected tile.
     Restart,
 
@@ -308,8 +1025,63 @@ pub enum Msg {
     /// Adds a line to every tile.
     AddLineAll,
 
-    /// Adds the finish line to the tile.
-    AddFinishLine((u16, u16), bool),
+    /// Adds the finish line to the tile, with the run of the command that finished and its exit
+    /// code.
+    AddFinishLine((u16, u16), usize, Option<i32>),
+
+    /// Opens a prompt in the title of the selected tile.
+    OpenPrompt(PromptKind),
+
+    /// A key was pressed while the prompt is open.
+    PromptKey(Key),
+
+    /// Jumps to the next match of the search.
+    SearchNext,
+
+    /// Jumps to the previous match of the search.
+    SearchPrevious,
+
+    /// Enters copy mode in the selected tile.
+    CopyMode,
+
+    /// Cycles between the ways of showing the timestamps of the selected tile.
+    CycleTimestamps,
+
+    /// Links the selected tile to the other linked tiles, or unlinks it.
+    ToggleLink,
+
+    /// Switches between synchronizing the linked tiles by offset and by time.
+    CycleLinkMode,
+
+    /// Scrolls the selected tile to its next error or warning.
+    NextCounted,
+
+    /// Scrolls the selected tile to its previous error or warning.
+    PreviousCounted,
+
+    /// Cycles between the ways of showing the wide lines of the selected tile.
+    CycleWrap,
+
+    /// Scrolls the selected tile horizontally, to the left if the step is negative.
+    ScrollHorizontally(isize),
+
+    /// Saves the scrollback of the selected tile, with its escape sequences if true.
+    SaveScrollback(bool),
+
+    /// Saves the scrollback of the selected tile and opens it in a pager.
+    OpenScrollback,
+
+    /// Exports the tiles to HTML, with their whole scrollback if true.
+    ExportHtml(bool),
+
+    /// Folds or unfolds the section at the top of the selected tile.
+    ToggleSection,
+
+    /// Folds or unfolds all the sections of the selected tile.
+    ToggleAllSections,
+
+    /// A key was pressed while the selected tile is in copy mode.
+    CopyKey(Key),
 
     /// The program was asked to exit.
     Exit,
@@ -320,10 +1092,11 @@ pub fn main() -> io::Result<()> {
     let (sender, receiver) = channel();
 
     let args = env::args().skip(1).collect::<Vec<_>>();
+    let (options, args) = Options::parse(&args)?;
 
     let mut is_row_major = true;
 
-    for arg in &args {
+    for arg in args {
         if arg == "//" {
             is_row_major = false;
             break;
@@ -357,7 +1130,14 @@ pub fn main() -> io::Result<()> {
         })
         .collect::<Vec<_>>();
 
-    let mut term_size = terminal_size()?;
+    // Raw mode and the alternate screen only make sense in a terminal, e.g. not in CI
+    let plain = options.plain || !io::stdout().is_terminal();
+
+    let mut term_size = match terminal_size() {
+        Ok(size) => size,
+        Err(_) if plain => (80, 24),
+        Err(e) => return Err(e),
+    };
 
     let col_len = tiles.len() as u16;
 
@@ -375,26 +1155,31 @@ pub fn main() -> io::Result<()> {
             row.into_iter()
                 .map(|((i, j), tile)| {
                     let (p_i, p_j) = if is_row_major { (i, j) } else { (j, i) };
+                    let (tile_options, command) = options.tile.parse(tile)?;
 
-                    TileBuilder::new()
-                        .command(tile.into())
+                    Ok(TileBuilder::new()
+                        .command(command.into())
+                        .options(tile_options)
                         .coords((i as u16, j as u16))
                         .position((p_j as u16 * tile_size.0 + 1, p_i as u16 * tile_size.1 + 1))
                         .size(tile_size)
                         .sender(sender.clone())
                         .build()
-                        .unwrap()
+                        .unwrap())
                 })
-                .collect::<Vec<_>>()
+                .collect::<io::Result<Vec<_>>>()
         })
-        .collect::<Vec<_>>();
+        .collect::<io::Result<Vec<_>>>()?;
+
+    if plain {
+        return plain::run(tiles, receiver);
+    }
 
-    let stdin = stdin();
     let stdout = stdout().into_raw_mode()?;
     let stdout = stdout.into_alternate_screen()?;
     let stdout = MouseTerminal::from(stdout);
 
-    let mut multiview = Multiview::new(stdout, tiles)?;
+    let mut multiview = Multiview::new(stdout, tiles, options)?;
     multiview.render(true)?;
 
     for row in &mut multiview.tiles {
@@ -403,40 +1188,37 @@ pub fn main() -> io::Result<()> {
         }
     }
 
+    let input = multiview.input.clone();
+
+    // The thread stops at the end of the input, or if it can't be read anymore
     thread::spawn(move || {
-        for c in stdin.events() {
-            let evt = c.unwrap();
-            match evt {
-                Event::Key(Key::Esc) | Event::Key(Key::Ctrl('c')) | Event::Key(Key::Char('q')) => {
-                    sender.send(Msg::Exit).unwrap()
+        while let Ok(Some(events)) = input.read(DELAY) {
+            for evt in events {
+                match evt {
+                    // The meaning of keys depends on the state of the multiview, e.g. if a prompt
+                    // is open
+                    Event::Key(key) => sender.send(Msg::Key(key)).unwrap(),
+                    Event::Mouse(MouseEvent::Press(p, x, y)) => match p {
+                        MouseButton::WheelUp => sender.send(Msg::Wheel(x, y, -3)).unwrap(),
+                        MouseButton::WheelDown => sender.send(Msg::Wheel(x, y, 3)).unwrap(),
+                        MouseButton::Left => sender.send(Msg::Click(x, y)).unwrap(),
+                        _ => (),
+                    },
+                    Event::Mouse(MouseEvent::Hold(x, y)) => sender.send(Msg::Drag(x, y)).unwrap(),
+                    Event::Mouse(MouseEvent::Release(x, y)) => {
+                        sender.send(Msg::Release(x, y)).unwrap()
+                    }
+
+                    _ => {}
                 }
-                Event::Key(Key::Char('r')) => sender.send(Msg::Restart).unwrap(),
-                Event::Key(Key::Char('R')) => sender.send(Msg::RestartAll).unwrap(),
-                Event::Key(Key::Char('k')) => sender.send(Msg::Kill).unwrap(),
-                Event::Key(Key::Char('K')) => sender.send(Msg::KillAll).unwrap(),
-                Event::Key(Key::Char('l')) => sender.send(Msg::AddLine).unwrap(),
-                Event::Key(Key::Char('L')) => sender.send(Msg::AddLineAll).unwrap(),
-                Event::Key(Key::Down) => sender.send(Msg::ScrollDown(1)).unwrap(),
-                Event::Key(Key::Up) => sender.send(Msg::ScrollUp(1)).unwrap(),
-                Event::Key(Key::End) => sender.send(Msg::ScrollFullDown).unwrap(),
-                Event::Key(Key::Home) => sender.send(Msg::ScrollFullUp).unwrap(),
-                Event::Mouse(MouseEvent::Press(p, x, y)) => match p {
-                    MouseButton::WheelUp => sender.send(Msg::ScrollUp(3)).unwrap(),
-                    MouseButton::WheelDown => sender.send(Msg::ScrollDown(3)).unwrap(),
-                    MouseButton::Left => sender.send(Msg::Click(x, y)).unwrap(),
-                    _ => (),
-                },
-
-                _ => {}
             }
         }
     });
 
     loop {
         if let Ok(msg) = receiver.recv_timeout(DELAY) {
-            let is_exit = msg == Msg::Exit;
             multiview.manage_msg(msg)?;
-            if is_exit {
+            if multiview.exiting {
                 break;
             }
         }
@@ -446,6 +1228,10 @@ pub fn main() -> io::Result<()> {
         if term_size != new_term_size {
             term_size = new_term_size;
 
+            if let Some(recorder) = multiview.recorder.as_ref() {
+                recorder.resize(term_size);
+            }
+
             for (i, row) in multiview.tiles.iter_mut().enumerate() {
                 let row_len = row.len() as u16;
 
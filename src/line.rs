@@ -0,0 +1,523 @@
+//! This module contains the lines of output of the tiles.
+
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use termion::{color, cursor, style};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::ansi::{Action, Parser, Style};
+use crate::utils;
+
+/// A line of the output of a command, as displayed on a single row of its tile.
+#[derive(Clone, Debug)]
+pub struct Line {
+    /// The content of the line, including the escape sequences that we interpret.
+    pub content: String,
+
+    /// The style that is active at the beginning of the line.
+    pub style: Style,
+
+    /// When the first character of the line arrived.
+    pub timestamp: Option<SystemTime>,
+
+    /// The marker of the line, if it ends a section of the output.
+    pub marker: Option<Marker>,
+
+    /// The last column the tabs of the line can move to, which is the width the line is wrapped
+    /// at.
+    pub tab_end: u16,
+}
+
+impl Default for Line {
+    fn default() -> Line {
+        Line::new(Style::default())
+    }
+}
+
+/// A line that ends a section of the output, drawn as a horizontal rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Marker {
+    /// A separator added by the user.
+    Separator,
+
+    /// The end of a run of the command, with its exit code, which is `None` if the command was
+    /// interrupted or couldn't run.
+    Finish(Option<i32>),
+}
+
+impl Marker {
+    /// Returns the escape sequence of the color of the marker, green if the command succeeded
+    /// and red if it failed.
+    pub fn color(&self) -> &'static str {
+        match self {
+            Marker::Separator => "",
+            Marker::Finish(Some(0)) => color::Green.fg_str(),
+            Marker::Finish(_) => color::Red.fg_str(),
+        }
+    }
+
+    /// Returns the message written before the marker, e.g. the exit status of the command.
+    pub fn message(&self) -> String {
+        match self {
+            Marker::Separator => String::new(),
+            Marker::Finish(Some(0)) => String::from("Command finished successfully"),
+            Marker::Finish(Some(code)) => format!("Command failed with exit code {}", code),
+            Marker::Finish(None) => String::from("Command was interrupted"),
+        }
+    }
+
+    /// Renders the marker at the specified position, on `w` columns.
+    pub fn render(&self, (x, y): (u16, u16), w: u16) -> String {
+        format!(
+            "{}{}{}{} {}",
+            cursor::Goto(x, y),
+            style::Reset,
+            self.color(),
+            "─".repeat(w.saturating_sub(1) as usize),
+            style::Reset,
+        )
+    }
+}
+
+/// How the timestamps of the lines are shown in the gutter of a tile.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Timestamps {
+    /// The timestamps are not shown.
+    #[default]
+    Hidden,
+
+    /// The local time when the lines arrived.
+    Absolute,
+
+    /// The number of seconds between the start of the tile and the arrival of the lines.
+    Relative,
+}
+
+impl Timestamps {
+    /// Returns the next way of showing timestamps, to cycle between them.
+    pub fn next(self) -> Timestamps {
+        match self {
+            Timestamps::Hidden => Timestamps::Absolute,
+            Timestamps::Absolute => Timestamps::Relative,
+            Timestamps::Relative => Timestamps::Hidden,
+        }
+    }
+
+    /// Returns the number of columns of the gutter, including the space separating it from the
+    /// content.
+    pub fn width(self) -> u16 {
+        match self {
+            Timestamps::Hidden => 0,
+            Timestamps::Absolute | Timestamps::Relative => 13,
+        }
+    }
+
+    /// Formats a timestamp, `start` being the time when the tile started.
+    pub fn format(self, timestamp: SystemTime, start: SystemTime) -> String {
+        match self {
+            Timestamps::Hidden => String::new(),
+            Timestamps::Absolute => utils::format_time(timestamp),
+            Timestamps::Relative => {
+                let elapsed = timestamp.duration_since(start).unwrap_or_default();
+                format!("+{:>11.3}", elapsed.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// How the lines that are wider than a tile are shown.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Wrap {
+    /// The lines continue on the next rows.
+    #[default]
+    Hard,
+
+    /// The lines continue on the next rows, after a whitespace or a punctuation when possible.
+    Word,
+
+    /// The lines are cut at the edge of the tile, which can be scrolled horizontally.
+    Off,
+}
+
+impl Wrap {
+    /// Returns the next way of showing wide lines, to cycle between them.
+    pub fn next(self) -> Wrap {
+        match self {
+            Wrap::Hard => Wrap::Word,
+            Wrap::Word => Wrap::Off,
+            Wrap::Off => Wrap::Hard,
+        }
+    }
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Wrap, String> {
+        match s {
+            "hard" => Ok(Wrap::Hard),
+            "word" => Ok(Wrap::Word),
+            "off" => Ok(Wrap::Off),
+            _ => Err(format!("unknown wrap mode: {}", s)),
+        }
+    }
+}
+
+/// An element of the layout of a line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    /// A grapheme cluster written at a column.
+    Grapheme {
+        /// The column where the grapheme is written.
+        column: u16,
+
+        /// The grapheme.
+        grapheme: String,
+
+        /// The number of columns of the grapheme.
+        width: u16,
+    },
+
+    /// Some columns are erased.
+    Erase {
+        /// The first erased column.
+        start: u16,
+
+        /// The column after the last erased column.
+        end: u16,
+    },
+
+    /// The style changes.
+    Style {
+        /// The SGR sequence that changed the style.
+        raw: String,
+
+        /// The style after the change.
+        style: Style,
+    },
+}
+
+/// A part of a line that is rendered with a different style, e.g. a search match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Highlight {
+    /// The first highlighted column.
+    pub start: u16,
+
+    /// The column after the last highlighted column.
+    pub end: u16,
+
+    /// The style of the highlighted text.
+    pub style: Style,
+}
+
+impl Line {
+    /// Creates an empty line starting with the specified style.
+    pub fn new(style: Style) -> Line {
+        Line {
+            content: String::new(),
+            style,
+            timestamp: None,
+            marker: None,
+            tab_end: u16::MAX,
+        }
+    }
+
+    /// Returns whether the line ends with a line feed, i.e. whether the next line is not its
+    /// continuation.
+    pub fn is_terminated(&self) -> bool {
+        self.content.ends_with('\n')
+    }
+
+    /// Interprets the content of the line, and returns where each grapheme cluster is written.
+    ///
+    /// The columns are not bounded: erasing until the end of the line erases until `u16::MAX`.
+    pub fn layout(&self) -> Vec<Item> {
+        let mut items = vec![];
+        let mut style = self.style;
+        let mut parser = Parser::new();
+        let mut column: u16 = 0;
+
+        // The printable characters are buffered so that they are laid out by grapheme cluster
+        let mut text = String::new();
+
+        let mut chars = self.content.chars();
+
+        loop {
+            let action = chars.next().map(|c| parser.advance(c));
+
+            if !matches!(action, Some(Some(Action::Print(_))) | Some(None)) {
+                for grapheme in text.graphemes(true) {
+                    let width = utils::grapheme_width(grapheme);
+                    items.push(Item::Grapheme {
+                        column,
+                        grapheme: grapheme.to_string(),
+                        width,
+                    });
+                    column = column.saturating_add(width);
+                }
+                text.clear();
+            }
+
+            let action = match action {
+                Some(Some(action)) => action,
+                Some(None) => continue,
+                None => break,
+            };
+
+            match action {
+                Action::Print(c) => text.push(c),
+
+                Action::Control('\r') => column = 0,
+
+                Action::Control('\t') => {
+                    column = std::cmp::min(utils::next_tab_stop(column), self.tab_end)
+                }
+
+                Action::Control('\x08') => column = column.saturating_sub(1),
+
+                action @ Action::Csi { .. } if action.is_sgr() => {
+                    if let Action::Csi { raw, params, .. } = action {
+                        style.apply(&params);
+                        items.push(Item::Style { raw, style });
+                    }
+                }
+
+                Action::Csi {
+                    params, end: 'K', ..
+                } => {
+                    let (start, end) = match params.as_str() {
+                        "1" => (0, column.saturating_add(1)),
+                        "2" => (0, u16::MAX),
+                        _ => (column, u16::MAX),
+                    };
+                    items.push(Item::Erase { start, end });
+                }
+
+                _ => (),
+            }
+        }
+
+        items
+    }
+
+    /// Returns the visible grapheme clusters of the line with their columns, once everything that
+    /// was overwritten or erased is removed.
+    pub fn cells(&self) -> Vec<(u16, String)> {
+        self.styled_cells()
+            .into_iter()
+            .map(|(column, grapheme, _)| (column, grapheme))
+            .collect()
+    }
+
+    /// Returns the visible grapheme clusters of the line with their columns and the style they
+    /// were written with.
+    pub fn styled_cells(&self) -> Vec<(u16, String, Style)> {
+        let mut cells: Vec<Option<(String, Style)>> = vec![];
+        let mut style = self.style;
+
+        for item in self.layout() {
+            match item {
+                Item::Grapheme {
+                    column,
+                    grapheme,
+                    width,
+                } => {
+                    let end = column as usize + std::cmp::max(width, 1) as usize;
+                    if cells.len() < end {
+                        cells.resize(end, None);
+                    }
+                    cells[column as usize] = Some((grapheme, style));
+
+                    // The other columns of wide graphemes are empty
+                    for cell in &mut cells[column as usize + 1..end] {
+                        *cell = Some((String::new(), style));
+                    }
+                }
+
+                Item::Erase { start, end } => {
+                    let end = std::cmp::min(end as usize, cells.len());
+                    for cell in cells.iter_mut().take(end).skip(start as usize) {
+                        *cell = None;
+                    }
+                }
+
+                Item::Style { style: new, .. } => style = new,
+            }
+        }
+
+        cells
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, cell)| {
+                cell.filter(|(x, _)| !x.is_empty())
+                    .map(|(x, style)| (i as u16, x, style))
+            })
+            .collect()
+    }
+
+    /// Returns the visible grapheme clusters of the line like `styled_cells`, with a blank cell
+    /// in the default style for each column before the last cluster that was never written.
+    pub fn filled_cells(&self) -> Vec<(u16, String, Style)> {
+        let mut cells = vec![];
+        let mut column = 0;
+
+        for (first, grapheme, style) in self.styled_cells() {
+            // Columns that were never written are blank
+            for blank in column..first {
+                cells.push((blank, String::from(" "), Style::default()));
+            }
+
+            column = first + utils::grapheme_width(&grapheme);
+            cells.push((first, grapheme, style));
+        }
+
+        cells
+    }
+
+    /// Returns the number of columns of the line, up to the end of its last grapheme cluster.
+    pub fn width(&self) -> u16 {
+        self.cells()
+            .last()
+            .map(|(column, grapheme)| column + utils::grapheme_width(grapheme))
+            .unwrap_or(0)
+    }
+
+    /// Returns the visible text of the line, without escape sequences.
+    pub fn text(&self) -> String {
+        self.slice(0, u16::MAX)
+    }
+
+    /// Returns the visible text of the grapheme clusters starting between two columns, the end
+    /// being excluded.
+    pub fn slice(&self, start: u16, end: u16) -> String {
+        self.filled_cells()
+            .into_iter()
+            .filter(|(first, _, _)| start <= *first && *first < end)
+            .map(|(_, grapheme, _)| grapheme)
+            .collect()
+    }
+
+    /// Renders the columns of a line starting at `offset` at the specified position, on `w`
+    /// columns.
+    ///
+    /// Lines start with the style that was active when they were written, so that they look the
+    /// same whatever the scroll. The style changes of the columns that are scrolled out are still
+    /// applied, so that the first visible column has the right style.
+    pub fn render(
+        line: Option<&Line>,
+        (x, y): (u16, u16),
+        w: u16,
+        offset: u16,
+        highlights: &[Highlight],
+    ) -> String {
+        const DELETE_CHAR: char = ' ';
+
+        let mut buffer = format!("{}{}", cursor::Goto(x, y), style::Reset);
+
+        // The column of the cursor, and the first column that was not written
+        let mut cursor = 0;
+        let mut max_column = 0;
+
+        if let Some(line) = line {
+            let mut style = line.style;
+            let mut highlight: Option<Highlight> = None;
+
+            buffer.push_str(&style.sgr());
+
+            for item in line.layout() {
+                match item {
+                    Item::Grapheme {
+                        column,
+                        grapheme,
+                        width,
+                    } => {
+                        // Graphemes cut by the edges are not shown
+                        if column < offset
+                            || column.saturating_add(width) > offset.saturating_add(w)
+                        {
+                            continue;
+                        }
+
+                        // Highlights are on the columns of the line, not on the screen
+                        let current = highlights
+                            .iter()
+                            .find(|h| h.start <= column && column < h.end)
+                            .copied();
+
+                        let column = column - offset;
+
+                        // Cells skipped by tabs must be cleared if they were never written
+                        if column > max_column {
+                            buffer.push_str(&format!(
+                                "{}{}",
+                                cursor::Goto(x + max_column, y),
+                                DELETE_CHAR
+                                    .to_string()
+                                    .repeat((column - max_column) as usize)
+                            ));
+                            cursor = column;
+                        }
+
+                        if column != cursor {
+                            buffer.push_str(&format!("{}", cursor::Goto(x + column, y)));
+                        }
+
+                        if current != highlight {
+                            match current {
+                                Some(h) => buffer.push_str(&h.style.sgr()),
+                                None => buffer.push_str(&style.sgr()),
+                            }
+                            highlight = current;
+                        }
+
+                        buffer.push_str(&grapheme);
+                        cursor = column + width;
+                        max_column = std::cmp::max(max_column, cursor);
+                    }
+
+                    Item::Erase { start, end } => {
+                        let start = start.saturating_sub(offset);
+                        let end = std::cmp::min(end.saturating_sub(offset), w);
+                        if start < end {
+                            if highlight.take().is_some() {
+                                buffer.push_str(&style.sgr());
+                            }
+
+                            buffer.push_str(&format!(
+                                "{}{}",
+                                cursor::Goto(x + start, y),
+                                DELETE_CHAR.to_string().repeat((end - start) as usize)
+                            ));
+                            cursor = end;
+                            max_column = std::cmp::max(max_column, end);
+                        }
+                    }
+
+                    Item::Style { raw, style: new } => {
+                        style = new;
+
+                        // Highlights have their own style
+                        if highlight.is_none() {
+                            buffer.push_str(&raw);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Clear the end of the line
+        buffer.push_str(&format!(
+            "{}{}",
+            style::Reset,
+            cursor::Goto(x + max_column, y)
+        ));
+
+        for _ in max_column..w {
+            buffer.push(DELETE_CHAR);
+        }
+
+        buffer
+    }
+}
@@ -0,0 +1,119 @@
+//! This module contains the log files where the output of the tiles is written.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::ansi::{Action, Parser};
+use crate::utils;
+
+/// What is written to the log files.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LogMode {
+    /// The output exactly as the command wrote it, including its escape sequences.
+    #[default]
+    Raw,
+
+    /// The text of the output, without escape sequences.
+    Plain,
+}
+
+impl FromStr for LogMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogMode, String> {
+        match s {
+            "raw" => Ok(LogMode::Raw),
+            "plain" => Ok(LogMode::Plain),
+            _ => Err(format!("unknown log mode: {}", s)),
+        }
+    }
+}
+
+/// A writer to the log file of a tile.
+///
+/// The stdout and the stderr of a command are written to the same file from their own threads,
+/// so each thread has its own writer, with its own parser for the plain mode.
+pub struct Log {
+    /// The log file, shared by the writers of a run.
+    file: Arc<Mutex<File>>,
+
+    /// What is written to the file.
+    mode: LogMode,
+
+    /// The parser that removes the escape sequences in plain mode.
+    parser: Parser,
+}
+
+impl Log {
+    /// Opens the log file of a tile for a new run of its command, and writes a header.
+    ///
+    /// The file is appended to, so that the output of the previous runs is kept.
+    pub fn open(dir: &Path, name: &str, command: &str, mode: LogMode) -> io::Result<Log> {
+        fs::create_dir_all(dir)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{}.log", name)))?;
+
+        writeln!(
+            file,
+            "=== {} {} ===",
+            utils::format_date_time(SystemTime::now()),
+            command
+        )?;
+
+        Ok(Log {
+            file: Arc::new(Mutex::new(file)),
+            mode,
+            parser: Parser::new(),
+        })
+    }
+
+    /// Returns another writer to the same file.
+    pub fn split(&self) -> Log {
+        Log {
+            file: self.file.clone(),
+            mode: self.mode,
+            parser: Parser::new(),
+        }
+    }
+
+    /// Writes output of the command, given both as the bytes that were read and as the decoded
+    /// text.
+    ///
+    /// Logs are best effort: the command keeps running if they can't be written.
+    pub fn write(&mut self, bytes: &[u8], text: &str) {
+        let plain;
+        let bytes = match self.mode {
+            LogMode::Raw => bytes,
+            LogMode::Plain => {
+                plain = self.strip(text);
+                plain.as_bytes()
+            }
+        };
+
+        if let Ok(mut file) = self.file.lock() {
+            file.write_all(bytes).ok();
+        }
+    }
+
+    /// Removes the escape sequences and the control characters that are not part of the text.
+    fn strip(&mut self, text: &str) -> String {
+        let mut output = String::new();
+
+        for c in text.chars() {
+            match self.parser.advance(c) {
+                Some(Action::Print(c)) => output.push(c),
+                Some(Action::Control(c @ ('\n' | '\t'))) => output.push(c),
+                _ => (),
+            }
+        }
+
+        output
+    }
+}
@@ -0,0 +1,385 @@
+//! This module contains the parsing of the command line options.
+//!
+//! Options at the beginning of the command line apply to every tile, and options at the
+//! beginning of the command of a tile only apply to this tile. In both cases, `--` ends the
+//! options.
+
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use regex::Regex;
+
+use crate::ansi::{Color, Style};
+use crate::encoding::Encoding;
+use crate::line::Wrap;
+use crate::log::LogMode;
+
+/// The options that can be set for every tile, or for a single tile.
+#[derive(Clone, Debug)]
+pub struct TileOptions {
+    /// The encoding of the output of the command.
+    pub encoding: Encoding,
+
+    /// How long a tile must stay silent before new output is reported as activity.
+    pub activity_idle: Duration,
+
+    /// The maximum number of lines kept in the scrollback.
+    pub scrollback_lines: Option<usize>,
+
+    /// The maximum number of bytes kept in the scrollback.
+    pub scrollback_bytes: Option<usize>,
+
+    /// The number of lines kept in memory, older lines being written to a temporary file.
+    pub spill_after: Option<usize>,
+
+    /// The rules that highlight parts of the output.
+    pub highlights: Vec<HighlightRule>,
+
+    /// The regex matching the lines that are counted as errors.
+    pub error_pattern: Option<Regex>,
+
+    /// The regex matching the lines that are counted as warnings.
+    pub warning_pattern: Option<Regex>,
+
+    /// How the lines wider than the tile are shown.
+    pub wrap: Wrap,
+
+    /// The directory where the output of the command is logged, if it is logged.
+    pub log_dir: Option<PathBuf>,
+
+    /// What is written to the log files.
+    pub log_mode: LogMode,
+
+    /// The directory where the output of the command is recorded for asciinema, if it is
+    /// recorded.
+    pub record_dir: Option<PathBuf>,
+}
+
+impl Default for TileOptions {
+    fn default() -> TileOptions {
+        TileOptions {
+            encoding: Encoding::default(),
+            activity_idle: Duration::from_secs(5),
+            scrollback_lines: None,
+            scrollback_bytes: None,
+            spill_after: None,
+            highlights: vec![],
+            error_pattern: Regex::new(r"(?i)\berror\b").ok(),
+            warning_pattern: Regex::new(r"(?i)\bwarning\b").ok(),
+            wrap: Wrap::default(),
+            log_dir: None,
+            log_mode: LogMode::default(),
+            record_dir: None,
+        }
+    }
+}
+
+impl TileOptions {
+    /// Parses the options at the beginning of the command of a tile, and returns the options of
+    /// the tile with the remaining arguments.
+    pub fn parse<'a>(&self, args: &'a [String]) -> io::Result<(TileOptions, &'a [String])> {
+        let mut options = self.clone();
+        let args = parse_with(args, |name, value| options.parse_option(name, value))?;
+        Ok((options, args))
+    }
+
+    /// Parses a single option, and returns whether the option is a tile option.
+    fn parse_option(&mut self, name: &str, value: &mut Value) -> Result<bool, String> {
+        match name {
+            "--encoding" => self.encoding = value.parse()?,
+            "--activity-idle" => self.activity_idle = value.parse_seconds()?,
+            "--scrollback-lines" => self.scrollback_lines = Some(value.parse()?),
+            "--scrollback-bytes" => self.scrollback_bytes = Some(value.parse_size()?),
+            "--spill-after" => self.spill_after = Some(value.parse()?),
+            "--highlight" => self.highlights.push(value.parse()?),
+            "--error-pattern" => self.error_pattern = value.parse_regex()?,
+            "--warning-pattern" => self.warning_pattern = value.parse_regex()?,
+            "--wrap" => self.wrap = value.parse()?,
+            "--log-dir" => self.log_dir = Some(value.parse()?),
+            "--log-mode" => self.log_mode = value.parse()?,
+            "--record-dir" => self.record_dir = Some(value.parse()?),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+/// A rule that highlights the parts of the output matching a regex, e.g. `error|FAILED=bold,red`.
+#[derive(Clone, Debug)]
+pub struct HighlightRule {
+    /// The regex that is highlighted.
+    pub regex: Regex,
+
+    /// The style of the highlighted text.
+    pub style: Style,
+}
+
+impl FromStr for HighlightRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<HighlightRule, String> {
+        // The style never contains `=`, but the regex can
+        let (regex, style) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected REGEX=STYLE: {}", s))?;
+
+        let regex = Regex::new(regex).map_err(|e| e.to_string())?;
+
+        let mut parsed = Style::default();
+        for name in style.split(',').map(str::trim) {
+            match name {
+                "bold" => parsed.bold = true,
+                "dim" => parsed.dim = true,
+                "italic" => parsed.italic = true,
+                "underline" => parsed.underline = true,
+                "blink" => parsed.blink = true,
+                "reverse" => parsed.reverse = true,
+                "strikethrough" => parsed.strikethrough = true,
+                _ => match name.strip_prefix("on-") {
+                    Some(color) => parsed.bg = Some(parse_color(color)?),
+                    None => parsed.fg = Some(parse_color(name)?),
+                },
+            }
+        }
+
+        Ok(HighlightRule {
+            regex,
+            style: parsed,
+        })
+    }
+}
+
+/// Parses the name of a color, e.g. `red`, `bright-red` or `208`.
+fn parse_color(name: &str) -> Result<Color, String> {
+    const COLORS: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+
+    if let Ok(index) = name.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+
+    let (name, offset) = match name.strip_prefix("bright-") {
+        Some(name) => (name, 8),
+        None => (name, 0),
+    };
+
+    COLORS
+        .iter()
+        .position(|x| *x == name)
+        .map(|i| Color::Indexed(i as u8 + offset))
+        .ok_or_else(|| format!("unknown style: {}", name))
+}
+
+/// How the bells and activity of background tiles are forwarded to the terminal.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Notify {
+    /// The notifications are only shown on the border of the tiles.
+    #[default]
+    None,
+
+    /// A bell is sent to the terminal.
+    Bell,
+
+    /// A desktop notification is sent with OSC 9.
+    Osc9,
+
+    /// A desktop notification is sent with OSC 777.
+    Osc777,
+}
+
+impl FromStr for Notify {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Notify, String> {
+        match s {
+            "none" => Ok(Notify::None),
+            "bell" => Ok(Notify::Bell),
+            "osc9" => Ok(Notify::Osc9),
+            "osc777" => Ok(Notify::Osc777),
+            _ => Err(format!("unknown notification: {}", s)),
+        }
+    }
+}
+
+/// The options of tileview.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The default options of the tiles.
+    pub tile: TileOptions,
+
+    /// How the bells and activity of background tiles are forwarded to the terminal.
+    pub notify: Notify,
+
+    /// Whether using the mouse on a tile, e.g. scrolling it, also selects it.
+    pub focus_follows_mouse: bool,
+
+    /// Whether the output of the commands is printed line by line instead of in tiles.
+    pub plain: bool,
+
+    /// The file where the screen is recorded for asciinema, if it is recorded.
+    pub record: Option<PathBuf>,
+}
+
+impl Options {
+    /// Parses the options at the beginning of the command line, and returns them with the
+    /// remaining arguments.
+    pub fn parse(args: &[String]) -> io::Result<(Options, &[String])> {
+        let mut options = Options::default();
+        let args = parse_with(args, |name, value| options.parse_option(name, value))?;
+        Ok((options, args))
+    }
+
+    /// Parses a single option, and returns whether the option is known.
+    fn parse_option(&mut self, name: &str, value: &mut Value) -> Result<bool, String> {
+        match name {
+            "--notify" => self.notify = value.parse()?,
+            "--focus-follows-mouse" => self.focus_follows_mouse = value.flag()?,
+            "--plain" => self.plain = value.flag()?,
+            "--record" => self.record = Some(value.parse()?),
+            _ => return self.tile.parse_option(name, value),
+        }
+
+        Ok(true)
+    }
+}
+
+/// The value of an option, either given as `--name=value` or `--name value`.
+pub struct Value<'a> {
+    /// The name of the option.
+    name: &'a str,
+
+    /// The value if it was given with `--name=value`.
+    inline: Option<&'a str>,
+
+    /// The arguments of the command line.
+    args: &'a [String],
+
+    /// The index of the next argument.
+    index: &'a mut usize,
+}
+
+impl Value<'_> {
+    /// Returns the value of the option.
+    pub fn get(&mut self) -> Result<&str, String> {
+        if let Some(value) = self.inline {
+            return Ok(value);
+        }
+
+        match self.args.get(*self.index) {
+            Some(value) => {
+                *self.index += 1;
+                Ok(value)
+            }
+            None => Err(format!("missing value for option {}", self.name)),
+        }
+    }
+
+    /// Parses the value of the option.
+    pub fn parse<T: FromStr>(&mut self) -> Result<T, String>
+    where
+        T::Err: Display,
+    {
+        let name = self.name;
+        self.get()?
+            .parse()
+            .map_err(|e| format!("invalid value for option {}: {}", name, e))
+    }
+
+    /// Parses the value of an option that doesn't need a value, which is true unless a value is
+    /// given with `--name=false`.
+    pub fn flag(&mut self) -> Result<bool, String> {
+        match self.inline {
+            Some(_) => self.parse(),
+            None => Ok(true),
+        }
+    }
+
+    /// Parses the value of the option as a regex, an empty value meaning no regex.
+    pub fn parse_regex(&mut self) -> Result<Option<Regex>, String> {
+        let name = self.name;
+        match self.get()? {
+            "" => Ok(None),
+            value => Regex::new(value)
+                .map(Some)
+                .map_err(|e| format!("invalid value for option {}: {}", name, e)),
+        }
+    }
+
+    /// Parses the value of the option as a size in bytes, with an optional `K`, `M` or `G` suffix.
+    pub fn parse_size(&mut self) -> Result<usize, String> {
+        let name = self.name;
+        let value = self.get()?;
+
+        let (number, multiplier) = match value.char_indices().last() {
+            Some((i, 'k' | 'K')) => (&value[..i], 1 << 10),
+            Some((i, 'm' | 'M')) => (&value[..i], 1 << 20),
+            Some((i, 'g' | 'G')) => (&value[..i], 1 << 30),
+            _ => (value, 1),
+        };
+
+        number
+            .parse::<usize>()
+            .map(|x| x * multiplier)
+            .map_err(|e| format!("invalid value for option {}: {}", name, e))
+    }
+
+    /// Parses the value of the option as a number of seconds.
+    pub fn parse_seconds(&mut self) -> Result<Duration, String> {
+        let name = self.name;
+        let seconds = self.parse::<f64>()?;
+        Duration::try_from_secs_f64(seconds)
+            .map_err(|e| format!("invalid value for option {}: {}", name, e))
+    }
+}
+
+/// Parses the options at the beginning of the arguments and returns the remaining arguments.
+///
+/// `f` parses a single option, and returns whether it knows the option.
+fn parse_with<F>(args: &[String], mut f: F) -> io::Result<&[String]>
+where
+    F: FnMut(&str, &mut Value) -> Result<bool, String>,
+{
+    let mut index = 0;
+
+    while let Some(arg) = args.get(index) {
+        index += 1;
+
+        if arg == "--" {
+            break;
+        }
+
+        if !arg.starts_with("--") {
+            index -= 1;
+            break;
+        }
+
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+
+        let mut value = Value {
+            name,
+            inline,
+            args,
+            index: &mut index,
+        };
+
+        let known =
+            f(name, &mut value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        if !known {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown option: {}", name),
+            ));
+        }
+    }
+
+    Ok(&args[index..])
+}
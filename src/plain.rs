@@ -0,0 +1,225 @@
+//! This module contains the plain mode, where the output of the commands is printed line by line
+//! with the name of their tile in front, e.g. when the output of tileview is not a terminal.
+
+use std::collections::HashMap;
+use std::io::{self, stdout, Write};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use termion::{color, style};
+
+use crate::ansi::{Action, Parser, Style};
+use crate::line::Marker;
+use crate::tile::Tile;
+use crate::Msg;
+
+/// The colors of the names of the tiles, used in turn.
+const COLORS: [u8; 6] = [6, 3, 2, 5, 4, 1];
+
+/// How long the output that arrives after the last command finished is waited for.
+const DRAIN_DELAY: Duration = Duration::from_millis(100);
+
+/// The output of a tile, printed line by line.
+struct Output {
+    /// The name of the tile, printed in front of its lines.
+    name: String,
+
+    /// The color of the name of the tile.
+    color: u8,
+
+    /// The parser of the output of the command.
+    parser: Parser,
+
+    /// The style at the beginning of the line being written.
+    style: Style,
+
+    /// The style at the end of the line being written.
+    current_style: Style,
+
+    /// The line being written, with its SGR sequences.
+    line: String,
+
+    /// Whether the last character was a carriage return, after which printed characters replace
+    /// the line.
+    carriage_return: bool,
+
+    /// Whether the command finished.
+    finished: bool,
+}
+
+impl Output {
+    /// Interprets output of the command, and prints the lines it completes.
+    fn push<W: Write>(&mut self, content: &str, width: usize, w: &mut W) -> io::Result<()> {
+        for c in content.chars() {
+            let action = match self.parser.advance(c) {
+                Some(action) => action,
+                None => continue,
+            };
+
+            // Progress bars rewrite their line, only the last version is kept
+            let overwrites = matches!(action, Action::Print(_));
+            if std::mem::take(&mut self.carriage_return) && overwrites {
+                self.line.clear();
+                self.style = self.current_style;
+            }
+
+            match action {
+                Action::Print(c) => self.line.push(c),
+                Action::Control('\n') => self.print_line(width, w)?,
+                Action::Control('\r') => self.carriage_return = true,
+                Action::Control('\t') => self.line.push('\t'),
+
+                action @ Action::Csi { .. } if action.is_sgr() => {
+                    if let Action::Csi { raw, params, .. } = action {
+                        self.current_style.apply(&params);
+                        self.line.push_str(&raw);
+                    }
+                }
+
+                // Other sequences and control characters can't be represented on a single line
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the line being written, with the name of the tile in front.
+    fn print_line<W: Write>(&mut self, width: usize, w: &mut W) -> io::Result<()> {
+        writeln!(
+            w,
+            "{}{}{:<width$} |{} {}{}{}",
+            style::Reset,
+            color::Fg(color::AnsiValue(self.color)),
+            self.name,
+            style::Reset,
+            self.style.sgr(),
+            self.line,
+            style::Reset,
+            width = width,
+        )?;
+
+        self.line.clear();
+        self.style = self.current_style;
+        Ok(())
+    }
+
+    /// Prints the end of the output of the command, and its exit status.
+    fn finish<W: Write>(&mut self, code: Option<i32>, width: usize, w: &mut W) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.print_line(width, w)?;
+        }
+
+        let marker = Marker::Finish(code);
+        self.style = Style::default();
+        self.current_style = Style::default();
+        self.line = format!("{}{}{}", style::Bold, marker.color(), marker.message());
+        self.print_line(width, w)?;
+
+        self.finished = true;
+        Ok(())
+    }
+}
+
+/// Runs the commands of the tiles, and prints their output until they all finish.
+pub fn run(mut tiles: Vec<Vec<Tile>>, receiver: Receiver<Msg>) -> io::Result<()> {
+    let tiles = tiles.iter_mut().flatten().collect::<Vec<_>>();
+
+    // Tiles running the same program are numbered
+    let mut outputs = HashMap::new();
+    for (index, tile) in tiles.iter().enumerate() {
+        let program = tile.program();
+        let name = if tiles.iter().filter(|t| t.program() == program).count() > 1 {
+            format!(
+                "{}.{}",
+                program,
+                1 + tiles[..index]
+                    .iter()
+                    .filter(|t| t.program() == program)
+                    .count()
+            )
+        } else {
+            program
+        };
+
+        outputs.insert(
+            tile.coords,
+            Output {
+                name,
+                color: COLORS[index % COLORS.len()],
+                parser: Parser::new(),
+                style: Style::default(),
+                current_style: Style::default(),
+                line: String::new(),
+                carriage_return: false,
+                finished: false,
+            },
+        );
+    }
+
+    let width = outputs
+        .values()
+        .map(|o| o.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let stdout = stdout();
+    let mut stdout = stdout.lock();
+
+    for tile in tiles {
+        tile.start();
+
+        // Commands that can't be run are finished right away, with their error in their tile
+        if tile.running == 0 {
+            let output = outputs.get_mut(&tile.coords).unwrap();
+            for (_, line) in tile.stdout.iter().filter(|(_, l)| l.marker.is_none()) {
+                output.push(&line.content, width, &mut stdout)?;
+            }
+            output.finished = true;
+        }
+    }
+
+    stdout.flush()?;
+
+    while outputs.values().any(|o| !o.finished) {
+        let msg = match receiver.recv() {
+            Ok(msg) => msg,
+            Err(_) => break,
+        };
+
+        handle(msg, &mut outputs, width, &mut stdout)?;
+    }
+
+    // The end of the stderr of the commands can arrive after they finished
+    while let Ok(msg) = receiver.recv_timeout(DRAIN_DELAY) {
+        handle(msg, &mut outputs, width, &mut stdout)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the output of a message sent by the commands.
+fn handle<W: Write>(
+    msg: Msg,
+    outputs: &mut HashMap<(u16, u16), Output>,
+    width: usize,
+    w: &mut W,
+) -> io::Result<()> {
+    match msg {
+        Msg::Stdout(coords, content) | Msg::Stderr(coords, content) => {
+            if let Some(output) = outputs.get_mut(&coords) {
+                output.push(&content, width, w)?;
+            }
+        }
+
+        Msg::AddFinishLine(coords, _, code) => {
+            if let Some(output) = outputs.get_mut(&coords) {
+                output.finish(code, width, w)?;
+            }
+        }
+
+        _ => (),
+    }
+
+    w.flush()
+}
@@ -0,0 +1,98 @@
+//! This module contains the recordings in the asciicast v2 format of asciinema, either of the
+//! whole screen of tileview or of the output of a single tile.
+//!
+//! A recording is a header with the size of the terminal, followed by one event per line with the
+//! time elapsed since the beginning of the recording, e.g. `[1.25, "o", "hello\r\n"]`.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// A writer to a recording.
+///
+/// The writers of a recording can be cloned, e.g. for the threads that read the stdout and the
+/// stderr of a command.
+#[derive(Clone)]
+pub struct Recorder {
+    /// The file of the recording.
+    file: Arc<Mutex<File>>,
+
+    /// When the recording started.
+    start: Instant,
+}
+
+impl Recorder {
+    /// Creates a recording of a terminal of the given size, and writes its header.
+    pub fn create(path: &Path, (w, h): (u16, u16), title: &str) -> io::Result<Recorder> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = File::create(path)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        writeln!(
+            file,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"title\": {}}}",
+            w,
+            h,
+            timestamp,
+            json_string(title)
+        )?;
+
+        Ok(Recorder {
+            file: Arc::new(Mutex::new(file)),
+            start: Instant::now(),
+        })
+    }
+
+    /// Records output written to the terminal.
+    pub fn output(&self, data: &str) {
+        if !data.is_empty() {
+            self.event("o", data);
+        }
+    }
+
+    /// Records a resize of the terminal.
+    pub fn resize(&self, (w, h): (u16, u16)) {
+        self.event("r", &format!("{}x{}", w, h));
+    }
+
+    /// Writes an event, timed from the beginning of the recording.
+    ///
+    /// Recordings are best effort: tileview keeps running if they can't be written.
+    fn event(&self, kind: &str, data: &str) {
+        let time = self.start.elapsed().as_secs_f64();
+
+        if let Ok(mut file) = self.file.lock() {
+            writeln!(file, "[{:.6}, \"{}\", {}]", time, kind, json_string(data)).ok();
+        }
+    }
+}
+
+/// Returns a string as a JSON string, with its quotes.
+pub fn json_string(s: &str) -> String {
+    let mut output = String::with_capacity(s.len() + 2);
+    output.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+    output
+}
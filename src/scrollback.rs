@@ -0,0 +1,488 @@
+//! This module contains the buffer that stores the output of a tile.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::ansi::Style;
+use crate::line::{Line, Marker};
+
+/// The number of lines that are written to or read from the disk at once.
+const PAGE_SIZE: usize = 256;
+
+/// The number of bytes that are moved at once when the file of the spilled lines is compacted.
+const COMPACT_CHUNK: usize = 1 << 20;
+
+/// The separator between the fields of a line written on the disk.
+///
+/// Control characters other than the ones used for the layout are never stored in lines, so this
+/// one can't appear in their content.
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// The limits of a scrollback.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Limits {
+    /// The maximum number of lines.
+    pub max_lines: Option<usize>,
+
+    /// The maximum number of bytes.
+    pub max_bytes: Option<usize>,
+
+    /// The number of lines kept in memory, older lines being written to the disk.
+    pub spill_after: Option<usize>,
+}
+
+/// The lines of a scrollback that were written to the disk.
+struct Spill {
+    /// The temporary file where the lines are written.
+    file: File,
+
+    /// The offset, the length in the file and the number of bytes of the content of each line.
+    index: VecDeque<(u64, usize, usize)>,
+
+    /// The length of the file.
+    end: u64,
+
+    /// The last lines that were read from the file, with the absolute index of the first one.
+    cache: RefCell<(usize, Vec<Line>)>,
+}
+
+/// The lines of the output of a tile.
+///
+/// The scrollback can be limited in number of lines or in bytes, in which case the oldest lines
+/// are dropped when a new line arrives and the limit is exceeded. It can also write its oldest
+/// lines to a temporary file and read them back when they are needed.
+pub struct Scrollback {
+    /// The lines in memory, the last one being the line currently written.
+    lines: VecDeque<Line>,
+
+    /// The oldest lines, if they were written to the disk.
+    spill: Option<Spill>,
+
+    /// The number of bytes of the lines, excluding the line currently written.
+    bytes: usize,
+
+    /// The limits of the scrollback.
+    limits: Limits,
+
+    /// The number of lines that were dropped since the creation of the scrollback.
+    evicted: usize,
+}
+
+impl Scrollback {
+    /// Creates a scrollback with an empty line.
+    pub fn new(limits: Limits) -> Scrollback {
+        Scrollback {
+            lines: VecDeque::from([Line::new(Style::default())]),
+            spill: None,
+            bytes: 0,
+            limits,
+            evicted: 0,
+        }
+    }
+
+    /// Creates an empty scrollback with the same limits.
+    pub fn empty_clone(&self) -> Scrollback {
+        Scrollback::new(self.limits)
+    }
+
+    /// Returns the number of lines written to the disk.
+    fn spilled(&self) -> usize {
+        self.spill.as_ref().map(|x| x.index.len()).unwrap_or(0)
+    }
+
+    /// Returns the number of lines.
+    pub fn len(&self) -> usize {
+        self.spilled() + self.lines.len()
+    }
+
+    /// Returns whether the scrollback is empty, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of lines that were dropped since the creation of the scrollback.
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    /// Returns a line, reading it from the disk if needed.
+    pub fn get(&self, index: usize) -> Option<Cow<'_, Line>> {
+        let spilled = self.spilled();
+
+        if index >= spilled {
+            return self.lines.get(index - spilled).map(Cow::Borrowed);
+        }
+
+        let spill = self.spill.as_ref()?;
+        let absolute = self.evicted + index;
+
+        let mut cache = spill.cache.borrow_mut();
+        let first = cache.0;
+
+        if first <= absolute && absolute < first + cache.1.len() {
+            return Some(Cow::Owned(cache.1[absolute - first].clone()));
+        }
+
+        // Read the page containing the line
+        let start = index - index % PAGE_SIZE;
+        let end = std::cmp::min(start + PAGE_SIZE, spilled);
+        let lines = spill.read(start, end).ok()?;
+        let line = lines[index - start].clone();
+        *cache = (self.evicted + start, lines);
+
+        Some(Cow::Owned(line))
+    }
+
+    /// Returns the line currently written.
+    pub fn last(&self) -> &Line {
+        self.lines.back().unwrap()
+    }
+
+    /// Returns the line currently written.
+    pub fn last_mut(&mut self) -> &mut Line {
+        self.lines.back_mut().unwrap()
+    }
+
+    /// Iterates over the lines with their indices, reading them from the disk if needed.
+    ///
+    /// The lines that can't be read from the disk are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Cow<'_, Line>)> {
+        (0..self.len()).filter_map(move |i| self.get(i).map(|line| (i, line)))
+    }
+
+    /// Pushes a new line, and returns the number of lines that were dropped to respect the limits.
+    pub fn push(&mut self, line: Line) -> usize {
+        self.bytes += self.lines.back().map(|x| x.content.len()).unwrap_or(0);
+        self.lines.push_back(line);
+
+        let mut evicted = 0;
+
+        // The line currently written is never dropped
+        while self.len() > 1 && self.exceeds_limits() {
+            self.bytes -= match self.spill.as_mut().and_then(|x| x.index.pop_front()) {
+                Some((_, _, bytes)) => bytes,
+                None => self.lines.pop_front().unwrap().content.len(),
+            };
+            evicted += 1;
+        }
+
+        self.evicted += evicted;
+
+        // The space of the lines dropped from the disk is reclaimed, the file is best effort
+        if let Some(spill) = self.spill.as_mut().filter(|_| evicted > 0) {
+            spill.compact().ok();
+        }
+
+        if let Some(spill_after) = self.limits.spill_after {
+            // Lines are written by pages to avoid writing to the disk on every line
+            if self.lines.len() >= std::cmp::max(spill_after, 1) + PAGE_SIZE {
+                self.spill_page();
+            }
+        }
+
+        evicted
+    }
+
+    /// Writes the oldest page of lines in memory to the disk.
+    fn spill_page(&mut self) {
+        if self.spill.is_none() {
+            self.spill = tempfile::tempfile().ok().map(|file| Spill {
+                file,
+                index: VecDeque::new(),
+                end: 0,
+                cache: RefCell::new((0, vec![])),
+            });
+        }
+
+        // If the file can't be created, the lines stay in memory
+        let spill = match self.spill.as_mut() {
+            Some(spill) => spill,
+            None => return,
+        };
+
+        let mut buffer = vec![];
+        let mut index = vec![];
+
+        for line in self.lines.iter().take(PAGE_SIZE) {
+            let encoded = encode(line);
+            index.push((
+                spill.end + buffer.len() as u64,
+                encoded.len(),
+                line.content.len(),
+            ));
+            buffer.extend_from_slice(&encoded);
+        }
+
+        if spill.file.write_all_at(&buffer, spill.end).is_ok() {
+            spill.end += buffer.len() as u64;
+            spill.index.extend(index);
+            self.lines.drain(0..PAGE_SIZE);
+        }
+    }
+
+    /// Returns whether the scrollback is bigger than its limits.
+    fn exceeds_limits(&self) -> bool {
+        self.limits
+            .max_lines
+            .map(|x| self.len() > x)
+            .unwrap_or(false)
+            || self
+                .limits
+                .max_bytes
+                .map(|x| self.bytes > x)
+                .unwrap_or(false)
+    }
+}
+
+impl Spill {
+    /// Moves the lines that are still in the scrollback to the beginning of the file, once the
+    /// lines that were dropped take at least as much space, and truncates the file.
+    ///
+    /// The lines are moved over the dropped ones only, so the file stays valid if it fails.
+    fn compact(&mut self) -> std::io::Result<()> {
+        let start = self.index.front().map(|x| x.0).unwrap_or(self.end);
+        let len = self.end - start;
+
+        if start == 0 || start < len {
+            return Ok(());
+        }
+
+        let mut buffer = vec![0; std::cmp::min(len as usize, COMPACT_CHUNK)];
+        let mut moved = 0;
+
+        while moved < len {
+            let chunk = std::cmp::min(len - moved, buffer.len() as u64) as usize;
+            self.file
+                .read_exact_at(&mut buffer[..chunk], start + moved)?;
+            self.file.write_all_at(&buffer[..chunk], moved)?;
+            moved += chunk as u64;
+        }
+
+        for (offset, _, _) in &mut self.index {
+            *offset -= start;
+        }
+
+        self.end = len;
+        self.file.set_len(len)
+    }
+
+    /// Reads the lines from `start` to `end` from the disk.
+    fn read(&self, start: usize, end: usize) -> std::io::Result<Vec<Line>> {
+        let (offset, _, _) = self.index[start];
+        let (last_offset, last_len, _) = self.index[end - 1];
+
+        let mut buffer = vec![0; (last_offset + last_len as u64 - offset) as usize];
+        self.file.read_exact_at(&mut buffer, offset)?;
+
+        Ok(self
+            .index
+            .range(start..end)
+            .map(|(line_offset, len, _)| {
+                let line_start = (line_offset - offset) as usize;
+                decode(&buffer[line_start..line_start + len])
+            })
+            .collect())
+    }
+}
+
+/// Encodes a line to write it to the disk.
+fn encode(line: &Line) -> Vec<u8> {
+    let sgr = line.style.sgr();
+
+    // Only keep the parameters of the SGR sequence
+    let params = &sgr[2..sgr.len() - 1];
+
+    // The timestamp is stored in milliseconds since the epoch, and is empty if there is none
+    let timestamp = line
+        .timestamp
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .map(|x| x.as_millis().to_string())
+        .unwrap_or_default();
+
+    let marker = match line.marker {
+        None => String::new(),
+        Some(Marker::Separator) => String::from("-"),
+        Some(Marker::Finish(code)) => {
+            format!("={}", code.map(|x| x.to_string()).unwrap_or_default())
+        }
+    };
+
+    // The end of the tabs is empty if they are not bounded
+    let tab_end = Some(line.tab_end)
+        .filter(|x| *x != u16::MAX)
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+
+    [params, &timestamp, &marker, &tab_end, &line.content]
+        .join(&FIELD_SEPARATOR.to_string())
+        .into_bytes()
+}
+
+/// Decodes a line read from the disk.
+fn decode(bytes: &[u8]) -> Line {
+    let encoded = String::from_utf8_lossy(bytes);
+    let mut fields = encoded.splitn(5, FIELD_SEPARATOR);
+    let params = fields.next().unwrap_or("");
+    let timestamp = fields.next().unwrap_or("");
+    let marker = fields.next().unwrap_or("");
+    let tab_end = fields.next().unwrap_or("");
+    let content = fields.next().unwrap_or("");
+
+    let mut style = Style::default();
+    style.apply(params);
+
+    let timestamp = timestamp
+        .parse::<u64>()
+        .ok()
+        .map(|x| UNIX_EPOCH + Duration::from_millis(x));
+
+    let marker = match marker {
+        "-" => Some(Marker::Separator),
+        x if x.starts_with('=') => Some(Marker::Finish(x[1..].parse().ok())),
+        _ => None,
+    };
+
+    Line {
+        content: content.to_string(),
+        style,
+        timestamp,
+        marker,
+        tab_end: tab_end.parse().unwrap_or(u16::MAX),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::Color;
+
+    use super::*;
+
+    /// Returns a line with a style, a timestamp and a marker.
+    fn line(content: &str, marker: Option<Marker>) -> Line {
+        let mut style = Style::default();
+        style.apply("1;38;5;208;48;2;1;2;3");
+
+        Line {
+            content: content.to_string(),
+            style,
+            timestamp: Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)),
+            marker,
+            tab_end: 36,
+        }
+    }
+
+    /// Asserts that two lines are the same.
+    fn assert_same(actual: &Line, expected: &Line) {
+        assert_eq!(actual.content, expected.content);
+        assert_eq!(actual.style, expected.style);
+        assert_eq!(actual.timestamp, expected.timestamp);
+        assert_eq!(actual.marker, expected.marker);
+        assert_eq!(actual.tab_end, expected.tab_end);
+    }
+
+    #[test]
+    fn round_trip() {
+        for marker in [
+            None,
+            Some(Marker::Separator),
+            Some(Marker::Finish(Some(0))),
+            Some(Marker::Finish(Some(-1))),
+            Some(Marker::Finish(None)),
+        ] {
+            let line = line("\x1b[31mred\x1b[m and plain\n", marker);
+            assert_same(&decode(&encode(&line)), &line);
+        }
+
+        let decoded = decode(&encode(&line("", None)));
+        assert_eq!(decoded.style.fg, Some(Color::Indexed(208)));
+        assert_eq!(decoded.style.bg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn line_without_timestamp() {
+        let line = Line {
+            content: String::from("text\n"),
+            ..Line::default()
+        };
+
+        assert_same(&decode(&encode(&line)), &line);
+    }
+
+    #[test]
+    fn spilled_lines() {
+        let mut scrollback = Scrollback::new(Limits {
+            max_lines: None,
+            max_bytes: None,
+            spill_after: Some(1),
+        });
+
+        let lines = (0..3 * PAGE_SIZE)
+            .map(|i| line(&format!("line {}\n", i), None))
+            .collect::<Vec<_>>();
+
+        for line in &lines {
+            scrollback.push(line.clone());
+        }
+
+        // The scrollback starts with an empty line
+        assert!(scrollback.spilled() > 0);
+        for (index, line) in lines.iter().enumerate() {
+            assert_same(&scrollback.get(index + 1).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn spilled_lines_dropped() {
+        let mut scrollback = Scrollback::new(Limits {
+            max_lines: Some(2 * PAGE_SIZE),
+            max_bytes: None,
+            spill_after: Some(1),
+        });
+
+        let lines = (0..20 * PAGE_SIZE)
+            .map(|i| line(&format!("line {}\n", i), None))
+            .collect::<Vec<_>>();
+
+        for line in &lines {
+            scrollback.push(line.clone());
+        }
+
+        // The file holds at most twice the lines that are still spilled
+        let spill = scrollback.spill.as_ref().unwrap();
+        let len = spill.file.metadata().unwrap().len();
+        assert!(len <= 2 * (spill.end - spill.index[0].0));
+
+        let first = lines.len() - scrollback.len();
+        for (index, line) in lines[first..].iter().enumerate() {
+            assert_same(&scrollback.get(index).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn spilled_lines_unreadable() {
+        let mut scrollback = Scrollback::new(Limits {
+            max_lines: None,
+            max_bytes: None,
+            spill_after: Some(1),
+        });
+
+        for i in 0..3 * PAGE_SIZE {
+            scrollback.push(line(&format!("line {}\n", i), None));
+        }
+
+        let spill = scrollback.spill.as_ref().unwrap();
+        spill.file.set_len(0).unwrap();
+        *spill.cache.borrow_mut() = (0, vec![]);
+
+        // Only the lines in memory are left, with their own indices
+        let spilled = scrollback.spilled();
+        let lines = scrollback.iter().collect::<Vec<_>>();
+        assert_eq!(lines.len(), scrollback.len() - spilled);
+        assert_eq!(lines[0].0, spilled);
+        assert_same(&lines[0].1, scrollback.get(spilled).unwrap().as_ref());
+    }
+}
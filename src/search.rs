@@ -0,0 +1,240 @@
+//! This module contains the search in the output of the tiles.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+
+use crate::line::Line;
+use crate::utils;
+
+/// A match of a search, on a single line of a scrollback.
+///
+/// Matches spanning several lines of a scrollback are split in one match per line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Match {
+    /// The absolute index of the line, i.e. counting the lines dropped from the scrollback.
+    pub line: usize,
+
+    /// The first column of the match.
+    pub start: u16,
+
+    /// The column after the last column of the match.
+    pub end: u16,
+}
+
+/// A line of output as it was written by the command, i.e. before it was wrapped, without escape
+/// sequences.
+#[derive(Clone, Debug, Default)]
+pub struct LogicalLine {
+    /// The text of the line.
+    pub text: String,
+
+    /// The offset in the text, the index of the line in the scrollback, the column and the width
+    /// of each grapheme cluster of the text.
+    pub graphemes: Vec<(usize, usize, u16, u16)>,
+}
+
+impl LogicalLine {
+    /// Creates an empty logical line.
+    pub fn new() -> LogicalLine {
+        LogicalLine::default()
+    }
+
+    /// Appends a line of the scrollback to the logical line.
+    pub fn push(&mut self, index: usize, line: &Line) {
+        for (start, grapheme, _) in line.filled_cells() {
+            let width = utils::grapheme_width(&grapheme);
+            self.graphemes.push((self.text.len(), index, start, width));
+            self.text.push_str(&grapheme);
+        }
+    }
+
+    /// Returns the matches of a regex, `offset` being the absolute index of the first line of the
+    /// scrollback.
+    pub fn find(&self, regex: &Regex, offset: usize) -> Vec<Match> {
+        let mut matches: Vec<Match> = vec![];
+
+        for m in regex.find_iter(&self.text).filter(|m| !m.is_empty()) {
+            let first = self.graphemes.partition_point(|g| g.0 < m.start());
+
+            for &(_, index, column, width) in
+                self.graphemes[first..].iter().take_while(|g| g.0 < m.end())
+            {
+                let line = index + offset;
+                match matches.last_mut() {
+                    Some(last) if last.line == line && last.end == column => {
+                        last.end = column + width;
+                    }
+                    _ => matches.push(Match {
+                        line,
+                        start: column,
+                        end: column + width,
+                    }),
+                }
+            }
+        }
+
+        matches
+    }
+}
+
+/// A search in the output of a tile.
+pub struct Search {
+    /// The regex that is searched.
+    pub regex: Regex,
+
+    /// The matches, sorted.
+    pub matches: Vec<Match>,
+
+    /// The match the user jumped to.
+    pub current: Option<Match>,
+
+    /// The absolute index of the first line that still needs to be searched.
+    ///
+    /// The last line of output can still be written, so it is searched again when new output
+    /// arrives.
+    pub scanned: usize,
+}
+
+impl Search {
+    /// Creates a new search.
+    pub fn new(regex: Regex) -> Search {
+        Search {
+            regex,
+            matches: vec![],
+            current: None,
+            scanned: 0,
+        }
+    }
+
+    /// Forgets the matches, so that everything is searched again.
+    pub fn reset(&mut self) {
+        self.matches.clear();
+        self.current = None;
+        self.scanned = 0;
+    }
+
+    /// Returns the matches on a line.
+    pub fn matches_on(&self, line: usize) -> &[Match] {
+        let start = self.matches.partition_point(|m| m.line < line);
+        let end = self.matches.partition_point(|m| m.line <= line);
+        &self.matches[start..end]
+    }
+
+    /// Returns the index of the current match.
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+            .and_then(|c| self.matches.binary_search(&c).ok())
+    }
+
+    /// Returns the next match after the current match, or after the line `from` if there is no
+    /// current match, wrapping around the output.
+    pub fn next(&self, from: usize, forward: bool) -> Option<Match> {
+        let index = match (self.current, forward) {
+            (Some(current), true) => self.matches.partition_point(|m| *m <= current),
+            (Some(current), false) => self.matches.partition_point(|m| *m < current),
+            (None, true) => self.matches.partition_point(|m| m.line < from),
+            (None, false) => self.matches.partition_point(|m| m.line <= from),
+        };
+
+        if forward {
+            self.matches.get(index).or(self.matches.first()).copied()
+        } else {
+            index
+                .checked_sub(1)
+                .map(|i| self.matches[i])
+                .or(self.matches.last().copied())
+        }
+    }
+}
+
+/// A filter on the output of a tile, that only shows the lines matching a regex.
+pub struct Filter {
+    /// The regex that the lines must match.
+    pub regex: Regex,
+
+    /// The absolute indices of the lines of the scrollback that are shown, sorted.
+    ///
+    /// Logical lines are shown or hidden as a whole, so a long line matching the regex is shown
+    /// on all the lines it was wrapped on.
+    pub lines: Vec<usize>,
+
+    /// The absolute index of the first line that still needs to be filtered.
+    pub scanned: usize,
+}
+
+impl Filter {
+    /// Creates a new filter.
+    pub fn new(regex: Regex) -> Filter {
+        Filter {
+            regex,
+            lines: vec![],
+            scanned: 0,
+        }
+    }
+
+    /// Forgets the lines that were filtered, so that everything is filtered again.
+    pub fn reset(&mut self) {
+        self.lines.clear();
+        self.scanned = 0;
+    }
+
+    /// Returns the position in the filtered view of the first line shown at or after a line.
+    pub fn position(&self, line: usize) -> usize {
+        self.lines.partition_point(|l| *l < line)
+    }
+}
+
+/// A count of the logical lines of output matching a regex, e.g. the errors of a build.
+pub struct Counter {
+    /// The regex that the lines must match to be counted.
+    pub regex: Regex,
+
+    /// The absolute indices of the first lines of the logical lines that are counted, sorted.
+    pub lines: Vec<usize>,
+
+    /// The number of counted lines that were dropped from the scrollback.
+    pub dropped: usize,
+
+    /// The absolute index of the first line that still needs to be scanned.
+    pub scanned: usize,
+
+    /// The lines that arrived before this time are not counted.
+    pub since: SystemTime,
+}
+
+impl Counter {
+    /// Creates a new counter.
+    pub fn new(regex: Regex) -> Counter {
+        Counter {
+            regex,
+            lines: vec![],
+            dropped: 0,
+            scanned: 0,
+            since: UNIX_EPOCH,
+        }
+    }
+
+    /// Returns the number of lines that were counted.
+    pub fn count(&self) -> usize {
+        self.dropped + self.lines.len()
+    }
+
+    /// Forgets the lines that were counted, and only counts the lines arriving from now on.
+    pub fn reset(&mut self) {
+        self.lines.clear();
+        self.dropped = 0;
+        self.since = SystemTime::now();
+    }
+
+    /// Scans the whole scrollback again, e.g. after it was wrapped again.
+    pub fn rescan(&mut self) {
+        self.lines.clear();
+        self.scanned = 0;
+    }
+
+    /// Returns whether a logical line is counted, `timestamp` being the time when it arrived.
+    pub fn matches(&self, logical: &LogicalLine, timestamp: Option<SystemTime>) -> bool {
+        timestamp.map(|x| x >= self.since).unwrap_or(true) && self.regex.is_match(&logical.text)
+    }
+}
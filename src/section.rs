@@ -0,0 +1,38 @@
+//! This module contains the sections of the output of the tiles, separated by markers.
+
+use crate::line::Marker;
+
+/// A section of the output of a tile, ended by a marker.
+///
+/// The section starts after the marker of the previous section, so only its end is stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// The absolute index of the line holding the marker that ends the section.
+    pub end: usize,
+
+    /// The marker that ends the section.
+    pub marker: Marker,
+
+    /// The number of lines written in the section, as written by the command.
+    pub lines: usize,
+
+    /// Whether the section is folded, i.e. shown as a single summary row.
+    pub folded: bool,
+}
+
+impl Section {
+    /// Returns the summary of the section, shown instead of its lines when it is folded.
+    pub fn summary(&self) -> String {
+        let lines = match self.lines {
+            1 => String::from("1 line"),
+            n => format!("{} lines", n),
+        };
+
+        match self.marker {
+            Marker::Separator => format!("▶ {}", lines),
+            Marker::Finish(Some(0)) => format!("▶ ✔ finished successfully, {}", lines),
+            Marker::Finish(Some(code)) => format!("▶ ✖ failed with exit code {}, {}", code, lines),
+            Marker::Finish(None) => format!("▶ ✖ interrupted, {}", lines),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! This module contains the selection of text in the output of the tiles.
+
+/// A position in the output of a tile.
+///
+/// The line is an absolute index, i.e. counting the lines dropped from the scrollback, so that
+/// the position stays on the same text when new output arrives.
+pub type Position = (usize, u16);
+
+/// The state of the copy mode of a tile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Selection {
+    /// Where the selection started, if the user started selecting.
+    pub anchor: Option<Position>,
+
+    /// The position of the cursor, which is the other end of the selection.
+    pub cursor: Position,
+
+    /// Whether the selection is done by dragging the mouse, in which case it is copied when the
+    /// button is released.
+    pub mouse: bool,
+}
+
+impl Selection {
+    /// Creates a selection with the cursor at a position, and nothing selected yet.
+    pub fn new(cursor: Position) -> Selection {
+        Selection {
+            anchor: None,
+            cursor,
+            mouse: false,
+        }
+    }
+
+    /// Returns the first and the last selected positions, both included.
+    pub fn range(&self) -> Option<(Position, Position)> {
+        let anchor = self.anchor?;
+        Some((
+            std::cmp::min(anchor, self.cursor),
+            std::cmp::max(anchor, self.cursor),
+        ))
+    }
+
+    /// Returns the selected columns of a line, the end being excluded.
+    pub fn columns(&self, line: usize) -> Option<(u16, u16)> {
+        let ((first_line, first_column), (last_line, last_column)) = self.range()?;
+
+        if line < first_line || line > last_line {
+            return None;
+        }
+
+        let start = if line == first_line { first_column } else { 0 };
+        let end = if line == last_line {
+            last_column.saturating_add(1)
+        } else {
+            u16::MAX
+        };
+
+        Some((start, end))
+    }
+}
@@ -1,17 +1,31 @@
 //! This module contains everything related to tiles.
 
-use std::io::Read;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::mpsc::Sender;
 use std::thread;
+use std::time::{Instant, SystemTime};
 
 use pty_process::blocking::Command;
 use pty_process::blocking::Pty;
 
-use unicode_width::UnicodeWidthChar;
+use unicode_segmentation::UnicodeSegmentation;
 
 use termion::{color, cursor, style};
 
+use crate::ansi::{Action, Color, Parser, Style};
+use crate::encoding::Decoder;
+use crate::line::{Highlight, Line, Marker, Timestamps, Wrap};
+use crate::log::Log;
+use crate::options::TileOptions;
+use crate::record::Recorder;
+use crate::scrollback::{Limits, Scrollback};
+use crate::search::{Counter, Filter, LogicalLine, Search};
+use crate::section::Section;
+use crate::selection::{Position, Selection};
 use crate::{utils, Msg};
 
 /// A helper to build tiles.
@@ -19,6 +33,9 @@ pub struct TileBuilder {
     /// The command that the tile will run.
     pub command: Option<Vec<String>>,
 
+    /// The options of the tile.
+    pub options: Option<TileOptions>,
+
     /// The coordinates of the tile.
     pub coords: Option<(u16, u16)>,
 
@@ -32,11 +49,18 @@ pub struct TileBuilder {
     pub sender: Option<Sender<Msg>>,
 }
 
+impl Default for TileBuilder {
+    fn default() -> TileBuilder {
+        TileBuilder::new()
+    }
+}
+
 impl TileBuilder {
     /// Creates an empty tile builder.
     pub fn new() -> TileBuilder {
         TileBuilder {
             command: None,
+            options: None,
             coords: None,
             position: None,
             size: None,
@@ -51,6 +75,13 @@ impl TileBuilder {
         s
     }
 
+    /// Sets the options of the tile.
+    pub fn options(self, options: TileOptions) -> TileBuilder {
+        let mut s = self;
+        s.options = Some(options);
+        s
+    }
+
     /// Sets the coordinates of the tile.
     pub fn coords(self, coords: (u16, u16)) -> TileBuilder {
         let mut s = self;
@@ -83,34 +114,99 @@ impl TileBuilder {
     pub fn build(self) -> Option<Tile> {
         let (x, y) = self.position?;
         let (w, h) = self.size?;
+        let options = self.options.unwrap_or_default();
+        let stdout = Scrollback::new(Limits {
+            max_lines: options.scrollback_lines,
+            max_bytes: options.scrollback_bytes,
+            spill_after: options.spill_after,
+        });
+
+        let errors = options.error_pattern.clone().map(Counter::new);
+        let warnings = options.warning_pattern.clone().map(Counter::new);
+        let wrap = options.wrap;
 
         Some(Tile {
             command: self.command?,
+            options,
             coords: self.coords?,
             outer_position: (x, y),
             inner_position: (x + 2, y + 3),
             outer_size: (w, h),
             inner_size: (w - 4, h - 5),
             sender: self.sender?,
-            stdout: vec![String::new()],
+            stdout,
+            grapheme: String::new(),
+            grapheme_column: 0,
             scroll: 0,
-            counting: true,
+            parser: Parser::new(),
+            style: Style::default(),
             column_number: 0,
             pty: None,
             sticky: true,
+            search: None,
+            filter: None,
+            errors,
+            warnings,
+            link: None,
+            selection: None,
+            scrollbar_grab: None,
+            bell: false,
+            activity: false,
+            last_output: Instant::now(),
+            arrival: SystemTime::now(),
+            started: None,
+            timestamps: Timestamps::default(),
+            wrap,
+            hscroll: 0,
+            notice: None,
+            word_break: None,
+            sections: vec![],
+            running: 0,
+            run: 0,
+            recorder: None,
         })
     }
 }
 
+/// How the scroll of linked tiles is synchronized.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LinkMode {
+    /// The linked tiles scroll by the same number of lines.
+    #[default]
+    Offset,
+
+    /// The linked tiles show the lines that arrived at the same time.
+    Time,
+}
+
+/// A row shown in a tile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Row {
+    /// A line of the scrollback, by its index.
+    Line(usize),
+
+    /// The summary of a folded section, by its index in the sections of the tile.
+    Summary(usize),
+}
+
+/// The width at which lines are wrapped when wrapping is off, so that their columns still fit.
+const UNWRAPPED_WIDTH: u16 = 16384;
+
+/// The maximum number of columns of the filter shown on the border of a tile.
+const MAX_FILTER_LEN: u16 = 20;
+
 /// A tile with a command running inside it.
 pub struct Tile {
     /// The command that should be executed in the tile.
     pub command: Vec<String>,
 
+    /// The options of the tile.
+    pub options: TileOptions,
+
     /// Content of the command's stdout and stderr.
     ///
     /// We put both stdout and stderr here to avoid dealing with order between stdout and stderr.
-    pub stdout: Vec<String>,
+    pub stdout: Scrollback,
 
     /// The sender for the communication with the multiview.
     pub sender: Sender<Msg>,
@@ -133,19 +229,91 @@ pub struct Tile {
     /// The number of lines that the stdout is scrolled.
     pub scroll: isize,
 
-    /// Whether the characters arriving on stdout will move the cursor or not.
-    ///
-    /// Commands changing the text style won't move the cursor.
-    pub counting: bool,
+    /// The parser for the escape sequences arriving on stdout.
+    pub parser: Parser,
+
+    /// The style of the text at the end of stdout.
+    pub style: Style,
 
     /// The number of the current column.
     pub column_number: u16,
 
+    /// The last grapheme cluster of stdout, which can still be extended by the next characters.
+    pub grapheme: String,
+
+    /// The column where the last grapheme cluster starts.
+    pub grapheme_column: u16,
+
     /// The PTY of the command running in the tile.
     pub pty: Option<Pty>,
 
     /// Whether the tile should autoscroll.
     pub sticky: bool,
+
+    /// The search in the output of the tile.
+    pub search: Option<Search>,
+
+    /// The filter on the output of the tile.
+    pub filter: Option<Filter>,
+
+    /// The count of the errors in the output of the tile.
+    pub errors: Option<Counter>,
+
+    /// The count of the warnings in the output of the tile.
+    pub warnings: Option<Counter>,
+
+    /// How the tile is linked to the other linked tiles, if it is linked.
+    pub link: Option<LinkMode>,
+
+    /// The cursor and the selected text, if the tile is in copy mode.
+    pub selection: Option<Selection>,
+
+    /// The row of the thumb of the scrollbar where it was grabbed, while it is dragged.
+    pub scrollbar_grab: Option<u16>,
+
+    /// Whether the command rang the bell since the tile was last selected.
+    pub bell: bool,
+
+    /// Whether the command wrote output after being idle since the tile was last selected.
+    pub activity: bool,
+
+    /// The last time the command wrote output.
+    pub last_output: Instant,
+
+    /// When the output being pushed arrived.
+    pub arrival: SystemTime,
+
+    /// When the command was first started.
+    pub started: Option<SystemTime>,
+
+    /// How the timestamps of the lines are shown.
+    pub timestamps: Timestamps,
+
+    /// How the lines wider than the tile are shown.
+    pub wrap: Wrap,
+
+    /// The number of columns scrolled horizontally, when wrapping is off.
+    pub hscroll: u16,
+
+    /// A message for the user shown on the border of the tile until the next key is pressed,
+    /// e.g. where the scrollback was saved.
+    pub notice: Option<String>,
+
+    /// Where the line being written can be broken when words are wrapped, as the index in its
+    /// content, the column and the style after its last whitespace or punctuation.
+    pub word_break: Option<(usize, u16, Style)>,
+
+    /// The sections of the output, ended by markers, from the oldest to the newest.
+    pub sections: Vec<Section>,
+
+    /// The number of runs of the command whose finish line didn't arrive yet.
+    pub running: usize,
+
+    /// The number of times the command was started, which identifies its latest run.
+    pub run: usize,
+
+    /// The recording of the output of the tile, kept across the runs of its command.
+    pub recorder: Option<Recorder>,
 }
 
 impl Tile {
@@ -157,11 +325,16 @@ impl Tile {
             .map(|x| x.to_string())
             .collect::<Vec<_>>();
 
+        self.run += 1;
+
         let coords = self.coords;
+        let run = self.run;
         let clone = command.clone();
-        let size = self.inner_size;
+        let size = (self.content_width(), self.inner_size.1);
         let sender = self.sender.clone();
 
+        self.started.get_or_insert_with(SystemTime::now);
+
         let pty = Pty::new().unwrap();
         pty.resize(pty_process::Size::new(size.1, size.0)).unwrap();
 
@@ -182,33 +355,26 @@ impl Tile {
                     style::Reset,
                 );
 
-                sender.send(Msg::Stdout(coords, exit_string)).unwrap();
-
-                let mut line = String::new();
-                for _ in 0..size.0 - 1 {
-                    line.push('─');
-                }
-
-                sender
-                    .send(Msg::Stdout(
-                        coords,
-                        format!(
-                            "\n{}{}{}\n",
-                            color::Red.fg_str(),
-                            line,
-                            color::Reset.fg_str()
-                        ),
-                    ))
-                    .unwrap();
+                self.push_stdout(exit_string);
+                self.push_marker(Marker::Finish(None));
                 return;
             }
         };
 
+        self.running += 1;
+
+        let mut stdout_log = self.open_log();
+        let mut stderr_log = stdout_log.as_ref().map(Log::split);
+
+        let stdout_recorder = self.open_recorder();
+        let stderr_recorder = stdout_recorder.clone();
+
         let mut stdout = child.stdout.take().unwrap();
         let mut stderr = child.stderr.take().unwrap();
         let stderr_sender = sender.clone();
 
-        let coords = coords;
+        let mut stdout_decoder = Decoder::new(self.options.encoding);
+        let mut stderr_decoder = Decoder::new(self.options.encoding);
 
         thread::spawn(move || {
             loop {
@@ -219,122 +385,412 @@ impl Tile {
                     Ok(0) => break,
 
                     Ok(n) => {
-                        sender
-                            .send(Msg::Stderr(
-                                coords,
-                                String::from_utf8_lossy(&buffer[0..n]).to_string(),
-                            ))
-                            .unwrap();
+                        let output = stdout_decoder.decode(&buffer[0..n]);
+
+                        if let Some(log) = stdout_log.as_mut() {
+                            log.write(&buffer[0..n], &output);
+                        }
+
+                        // The output is not written to a terminal, so new lines don't return the
+                        // cursor to the start of the line by themselves
+                        if let Some(recorder) = stdout_recorder.as_ref() {
+                            recorder.output(&output.replace('\n', "\r\n"));
+                        }
+
+                        sender.send(Msg::Stderr(coords, output)).unwrap();
                     }
 
                     Err(_) => break,
                 }
             }
 
-            let code = child.wait().unwrap().code();
-
             sender
-                .send(Msg::Stdout(coords, String::from("\n")))
+                .send(Msg::Stdout(coords, stdout_decoder.finish()))
                 .unwrap();
 
-            let exit_string = match code {
-                Some(0) => format!(
-                    "{}{}Command finished successfully\r{}",
-                    style::Bold,
-                    color::Green.fg_str(),
-                    style::Reset,
-                ),
-                Some(x) => {
-                    format!(
-                        "{}{}Command failed with exit code {}\r{}",
-                        style::Bold,
-                        color::Red.fg_str(),
-                        x,
-                        style::Reset,
-                    )
-                }
-                None => {
-                    format!(
-                        "{}{}Command was interrupted\r{}",
-                        style::Bold,
-                        color::Red.fg_str(),
-                        style::Reset,
-                    )
+            let code = child.wait().unwrap().code();
+            sender.send(Msg::AddFinishLine(coords, run, code)).unwrap();
+        });
+
+        thread::spawn(move || {
+            loop {
+                let mut buffer = [0; 4096];
+                let result = stderr.read(&mut buffer);
+
+                match result {
+                    Ok(0) => break,
+
+                    Ok(n) => {
+                        let output = stderr_decoder.decode(&buffer[0..n]);
+
+                        if let Some(log) = stderr_log.as_mut() {
+                            log.write(&buffer[0..n], &output);
+                        }
+
+                        if let Some(recorder) = stderr_recorder.as_ref() {
+                            recorder.output(&output.replace('\n', "\r\n"));
+                        }
+
+                        stderr_sender.send(Msg::Stderr(coords, output)).unwrap();
+                    }
+
+                    Err(_) => break,
                 }
-            };
+            }
 
-            sender.send(Msg::Stdout(coords, exit_string)).unwrap();
-            sender
-                .send(Msg::AddFinishLine(coords, code == Some(0)))
+            stderr_sender
+                .send(Msg::Stderr(coords, stderr_decoder.finish()))
                 .unwrap();
         });
 
-        thread::spawn(move || loop {
-            let mut buffer = [0; 4096];
-            let result = stderr.read(&mut buffer);
+        self.pty = Some(pty);
+    }
+
+    /// Returns the name of the program run by the tile, without its directory.
+    pub fn program(&self) -> String {
+        Path::new(&self.command[0])
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of the files where the output of the tile is written, without their
+    /// extension.
+    ///
+    /// The files are named after the position of the tile and the program it runs, e.g.
+    /// `0-1-cargo`.
+    fn file_name(&self) -> String {
+        let program = self
+            .program()
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+
+        format!("{}-{}-{}", self.coords.0, self.coords.1, program)
+    }
+
+    /// Writes the whole scrollback of the tile to a new file named after the current time, in
+    /// the log directory if any, and returns its path.
+    ///
+    /// The file contains the text of the output, or the output with its escape sequences if
+    /// `ansi` is true. The lines that were wrapped are joined again, and start with their
+    /// timestamp if the timestamps are shown.
+    pub fn save_scrollback(&self, ansi: bool) -> io::Result<PathBuf> {
+        let dir = self.options.log_dir.clone().unwrap_or_default();
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(&dir)?;
+        }
+
+        let time = utils::format_file_time(SystemTime::now());
+
+        let extension = if ansi { "ansi" } else { "txt" };
+        let path = dir.join(format!("{}-{}.{}", self.file_name(), time, extension));
+
+        let mut file = BufWriter::new(File::create(&path)?);
+        let rule = "─".repeat(self.content_width().saturating_sub(1) as usize);
+        let gutter = self.timestamps.width() as usize;
+        let mut is_start = true;
+
+        for (index, line) in self.stdout.iter() {
+            // Only the first line of a logical line has a timestamp
+            if std::mem::replace(&mut is_start, line.marker.is_some() || line.is_terminated())
+                && gutter > 0
+                && !line.content.is_empty()
+            {
+                let timestamp = format!("{:<width$}", self.format_timestamp(&line), width = gutter);
+
+                // The timestamps are faint as in the gutter, and the line keeps its own style
+                match (line.marker, ansi) {
+                    (None, true) => write!(
+                        file,
+                        "{}{}{}{}{}",
+                        style::Reset,
+                        style::Faint,
+                        timestamp,
+                        style::Reset,
+                        line.style.sgr()
+                    )?,
+                    (Some(_), true) => {
+                        write!(file, "{}{}{}", style::Faint, timestamp, style::Reset)?
+                    }
+                    (_, false) => write!(file, "{}", timestamp)?,
+                }
+            }
 
-            match result {
-                Ok(0) => break,
+            match (line.marker, ansi) {
+                (Some(marker), true) => {
+                    writeln!(file, "{}{}{}", marker.color(), rule, style::Reset)?
+                }
+                (Some(_), false) => writeln!(file, "{}", rule)?,
 
-                Ok(n) => {
-                    stderr_sender
-                        .send(Msg::Stderr(
-                            coords,
-                            String::from_utf8_lossy(&buffer[0..n]).to_string(),
-                        ))
-                        .unwrap();
+                // The first line starts with the style of the lines that were dropped
+                (None, true) => {
+                    if index == 0 {
+                        write!(file, "{}", line.style.sgr())?;
+                    }
+                    write!(file, "{}", line.content)?;
                 }
 
-                Err(_) => break,
+                (None, false) => {
+                    write!(file, "{}", line.text())?;
+                    if line.is_terminated() {
+                        writeln!(file)?;
+                    }
+                }
             }
-        });
+        }
 
-        self.pty = Some(pty);
+        if ansi {
+            write!(file, "{}", style::Reset)?;
+        }
+
+        file.flush()?;
+        Ok(path)
+    }
+
+    /// Opens the log file of the tile for a new run, if the output is logged.
+    fn open_log(&mut self) -> Option<Log> {
+        let dir = self.options.log_dir.as_ref()?;
+        let name = self.file_name();
+        let command = self.command.join(" ");
+
+        match Log::open(dir, &name, &command, self.options.log_mode) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                self.push_stdout(format!(
+                    "{}{}Couldn't open log file: {}\r\n{}",
+                    style::Bold,
+                    color::Red.fg_str(),
+                    e,
+                    style::Reset,
+                ));
+                None
+            }
+        }
+    }
+
+    /// Opens the recording of the tile when its command first runs, if the output is recorded.
+    fn open_recorder(&mut self) -> Option<Recorder> {
+        if self.recorder.is_none() {
+            let dir = self.options.record_dir.as_ref()?;
+            let path = dir.join(format!("{}.cast", self.file_name()));
+            let size = (self.content_width(), self.inner_size.1);
+
+            match Recorder::create(&path, size, &self.command.join(" ")) {
+                Ok(recorder) => self.recorder = Some(recorder),
+                Err(e) => {
+                    self.push_stdout(format!(
+                        "{}{}Couldn't create recording: {}\r\n{}",
+                        style::Bold,
+                        color::Red.fg_str(),
+                        e,
+                        style::Reset,
+                    ));
+                    return None;
+                }
+            }
+        }
+
+        self.recorder.clone()
     }
 
     /// Push content into the stdout of the tile.
     pub fn push_stdout(&mut self, content: String) {
+        let now = Instant::now();
+        if now.duration_since(self.last_output) >= self.options.activity_idle {
+            self.activity = true;
+        }
+        self.last_output = now;
+        self.arrival = SystemTime::now();
+
+        self.push(&content);
+
+        self.update_sections();
+        self.update_search();
+        self.update_filter();
+        self.update_counters();
+
+        // Autoscroll whene content arrives on stdout
+        if self.sticky {
+            self.scroll = self.max_scroll();
+        }
+    }
+
+    /// Interprets content written by the command.
+    fn push(&mut self, content: &str) {
         for c in content.chars() {
-            if c == '\x1b' {
-                self.counting = false;
+            let action = match self.parser.advance(c) {
+                Some(action) => action,
+                None => continue,
+            };
+
+            // Only consecutive printable characters can form a grapheme cluster
+            if !matches!(action, Action::Print(_)) {
+                self.grapheme.clear();
             }
 
-            match c {
-                '\n' => {
-                    self.stdout.last_mut().unwrap().push(c);
-                    self.stdout.push(String::new());
-                    self.column_number = 0;
+            match action {
+                Action::Print(c) => self.push_char(c),
+
+                Action::Control(c) => self.push_control(c),
+
+                action @ Action::Csi { .. } if action.is_sgr() => {
+                    if let Action::Csi { raw, params, .. } = action {
+                        self.style.apply(&params);
+                        self.last_line_mut().content.push_str(&raw);
+                    }
                 }
 
-                '\r' => {
-                    self.stdout.last_mut().unwrap().push(c);
-                    self.column_number = 0;
+                Action::Csi { raw, end: 'K', .. } => {
+                    self.last_line_mut().content.push_str(&raw);
                 }
 
-                _ => {
-                    self.stdout.last_mut().unwrap().push(c);
+                // Other sequences (cursor movements, titles...) can't be represented in a tile
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns the line currently being written.
+    fn last_line_mut(&mut self) -> &mut Line {
+        let arrival = self.arrival;
+        let line = self.stdout.last_mut();
 
-                    // Emoji variation selectors have no length
-                    let is_variation_selector = c >= '\u{fe00}' && c <= '\u{fe0f}';
+        // Lines are created when the previous line ends, but they arrive with their first
+        // character
+        line.timestamp.get_or_insert(arrival);
+        line
+    }
 
-                    if self.counting && !is_variation_selector {
-                        self.column_number += 1;
-                        if self.column_number == self.inner_size.0 {
-                            self.stdout.push(String::new());
-                            self.column_number = 0;
-                        }
-                    }
-                }
+    /// Starts a new line, which begins with the current style.
+    fn new_line(&mut self) {
+        let evicted = self.stdout.push(Line::new(self.style)) as isize;
+
+        // When the output is filtered, the scroll is updated with the filter, and folded sections
+        // keep their summary until they are dropped
+        if self.filter.is_none() && !self.is_folded() {
+            self.scroll = std::cmp::max(0, self.scroll - evicted);
+        }
+
+        self.column_number = 0;
+        self.grapheme.clear();
+        self.word_break = None;
+    }
+
+    /// Push a printable character into the stdout of the tile.
+    fn push_char(&mut self, c: char) {
+        let w = self.wrap_width();
+
+        // Ascii characters never extend an ascii grapheme cluster, which avoids segmenting most of
+        // the output
+        let extended = if self.grapheme.is_empty() || (c.is_ascii() && self.grapheme.is_ascii()) {
+            None
+        } else {
+            let mut extended = self.grapheme.clone();
+            extended.push(c);
+            Some(extended).filter(|x| x.graphemes(true).count() == 1)
+        };
+
+        if let Some(extended) = extended {
+            // The character is part of the last grapheme cluster, e.g. a combining mark or a
+            // variation selector, which can make the cluster wider
+            self.grapheme = extended;
+            self.last_line_mut().content.push(c);
+
+            let width = utils::grapheme_width(&self.grapheme);
+
+            // The cluster doesn't fit anymore, move it to the next line
+            if self.grapheme_column + width > w && self.grapheme_column > 0 {
+                let grapheme = std::mem::take(&mut self.grapheme);
+                let content = &mut self.last_line_mut().content;
+                content.truncate(content.len() - grapheme.len());
+                self.new_line();
+                self.last_line_mut().content.push_str(&grapheme);
+                self.grapheme = grapheme;
+                self.grapheme_column = 0;
+            }
+
+            self.column_number = self.grapheme_column + width;
+        } else {
+            let width = utils::grapheme_width(c.encode_utf8(&mut [0; 4]));
+
+            // The line is wrapped only once the next grapheme cluster arrives, so that characters
+            // extending a cluster at the end of a line stay on that line
+            if self.column_number + width > w {
+                self.break_line();
             }
 
-            if c == 'm' || c == 'K' {
-                self.counting = true;
+            self.grapheme = c.to_string();
+            self.grapheme_column = self.column_number;
+            self.last_line_mut().content.push(c);
+            self.column_number += width;
+
+            if self.wrap == Wrap::Word && (c.is_whitespace() || c.is_ascii_punctuation()) {
+                self.word_break = Some((
+                    self.last_line_mut().content.len(),
+                    self.column_number,
+                    self.style,
+                ));
             }
         }
+    }
 
-        // Autoscroll whene content arrives on stdout
-        if self.sticky {
-            self.scroll = self.max_scroll();
+    /// Continues the line being written on a new line, after its last whitespace or punctuation
+    /// when words are wrapped.
+    fn break_line(&mut self) {
+        let (index, column, style) = match self.word_break {
+            Some(word_break) => word_break,
+            None => return self.new_line(),
+        };
+
+        let column_number = self.column_number;
+        let word = self.last_line_mut().content.split_off(index);
+        self.new_line();
+
+        let line = self.last_line_mut();
+        line.style = style;
+        line.content = word;
+        self.column_number = column_number - column;
+    }
+
+    /// Push a control character into the stdout of the tile.
+    fn push_control(&mut self, c: char) {
+        match c {
+            // Vertical tabs and form feeds behave like line feeds
+            '\n' | '\x0b' | '\x0c' => {
+                self.last_line_mut().content.push('\n');
+                self.new_line();
+            }
+
+            '\r' => {
+                self.last_line_mut().content.push(c);
+                self.column_number = 0;
+                self.word_break = None;
+            }
+
+            '\t' => {
+                let tab_end = self.wrap_width();
+                let line = self.last_line_mut();
+                line.content.push(c);
+                line.tab_end = tab_end;
+
+                let stop = utils::next_tab_stop(self.column_number);
+                self.column_number = std::cmp::min(stop, tab_end);
+            }
+
+            '\x08' => {
+                self.last_line_mut().content.push(c);
+                self.column_number = self.column_number.saturating_sub(1);
+                self.word_break = None;
+            }
+
+            '\x07' => self.bell = true,
+
+            // Other control characters (NUL, DEL...) are not displayed
+            _ => (),
         }
     }
 
@@ -347,36 +803,48 @@ impl Tile {
 
         let mut buffer = vec![];
 
-        let max_title_len = self.inner_size.0 - "Command: ".len() as u16;
+        let status = self.status();
+        let status_len = utils::text_width(&status);
 
-        let command_str = if command_str.len() > max_title_len as usize {
-            format!(
-                "{}...",
-                &command_str[0 as usize..max_title_len as usize - 3]
-            )
-        } else {
-            command_str
-        };
+        // Keep a space between the command and the status
+        let max_title_len = self
+            .inner_size
+            .0
+            .saturating_sub("Command: ".len() as u16 + status_len + (status_len > 0) as u16);
+
+        let command_str = utils::truncate(&command_str, max_title_len);
+
+        // The title is padded so that it erases the previous title
+        let title_len = " Command: ".len() as u16 + utils::text_width(&command_str);
+        let padding = (w - 2).saturating_sub(title_len + status_len) as usize;
 
         buffer.push(format!(
-            "{}{} {}Command: {}{}{}",
-            color::Reset.fg_str(),
+            "{}{} {}Command: {}{}{}{}{}{}{}",
+            style::Reset,
             cursor::Goto(x + 1, y + 1),
             style::Bold,
             command_str,
             style::Reset,
+            " ".repeat(padding),
+            style::Bold,
+            status,
+            style::Reset,
             cursor::Goto(x + 2, y + 3),
         ));
 
         if selected {
-            buffer.push(format!("{}", color::Green.fg_str()));
+            buffer.push(color::Green.fg_str().to_string());
+        } else if self.bell {
+            buffer.push(color::Red.fg_str().to_string());
+        } else if self.activity {
+            buffer.push(color::Yellow.fg_str().to_string());
         }
 
         buffer.push(utils::rect((x, y), (x + w - 1, y + h - 1)));
         buffer.push(format!("{}├", cursor::Goto(x, y + 2)));
 
         for _ in (x + 1)..(x + w) {
-            buffer.push(format!("─"));
+            buffer.push("─".to_string());
         }
 
         buffer.push(format!(
@@ -390,172 +858,70 @@ impl Tile {
 
     /// Renders the content of the tile.
     pub fn render_content(&self, selected: bool) -> String {
-        const DELETE_CHAR: char = ' ';
-
         let (x, y) = self.inner_position;
         let (w, h) = self.inner_size;
 
         let mut buffer = vec![];
 
-        let mut current_char_index = 0;
-        let mut max_char_index = 0;
+        let gutter = self.gutter_width();
+        let content_width = self.content_width();
 
-        let scroll = self.scroll as u16;
-        let mut line_index = scroll;
-        let mut last_line_index = line_index;
-
-        buffer.push(format!("{}", cursor::Goto(x, y)));
+        let rows = (0..=h)
+            .map(|row| self.view_row(self.scroll as usize + row as usize))
+            .collect::<Vec<_>>();
 
-        let mut iter = self
-            .stdout
+        let indices = rows
             .iter()
-            .skip(scroll as usize)
-            .take(h as usize + 1);
-
-        let mut line = iter.next().unwrap();
-        let mut char_iter = line.chars();
-
-        loop {
-            let c = match char_iter.next() {
-                Some(c) => c,
-                None => match iter.next() {
-                    Some(l) => {
-                        line = l;
-                        char_iter = line.chars();
-                        continue;
-                    }
-                    None => break,
-                },
-            };
-
-            if c == '\x1b' {
-                let mut subbuffer = vec![c];
-
-                loop {
-                    let next = match char_iter.next() {
-                        Some(c) => c,
-                        None => {
-                            match iter.next() {
-                                Some(l) => {
-                                    line = l;
-                                    char_iter = line.chars();
-                                    continue;
-                                }
-                                None => break,
-                            };
-                        }
-                    };
+            .map(|row| match row {
+                Some(Row::Line(index)) => Some(*index),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
 
-                    subbuffer.push(next);
+        let mut rules = self.rule_highlights(&indices);
 
-                    if next == 'm' || next == 'K' {
-                        break;
-                    }
-                }
+        for (row, (view_row, index)) in (0..=h).zip(rows.into_iter().zip(indices)) {
+            let line = index.and_then(|i| self.stdout.get(i));
 
-                match (subbuffer.get(0), subbuffer.get(1), subbuffer.get(2)) {
-                    (Some('\x1b'), Some('['), Some('K')) => {
-                        if current_char_index < w {
-                            let mut spaces = String::new();
-                            for _ in current_char_index..w {
-                                spaces.push(DELETE_CHAR);
-                            }
-                            buffer.push(format!(
-                                "{}{}{}",
-                                cursor::Goto(
-                                    x + current_char_index,
-                                    y + line_index as u16 - scroll
-                                ),
-                                spaces,
-                                cursor::Goto(
-                                    x + current_char_index,
-                                    y + line_index as u16 - scroll
-                                ),
-                            ));
-                        }
-                    }
-                    _ => buffer.push(subbuffer.into_iter().collect()),
-                }
+            if gutter > 0 {
+                buffer.push(self.render_gutter(index, (x, y + row)));
+            }
 
+            if let Some(Row::Summary(section)) = view_row {
+                buffer.push(self.render_summary(section, (x + gutter, y + row), content_width));
                 continue;
             }
 
-            match c {
-                '\n' => {
-                    let mut spaces = format!(
-                        "{}",
-                        cursor::Goto(x + max_char_index, y + line_index as u16 - scroll)
-                    );
-                    for _ in max_char_index..w {
-                        spaces.push(DELETE_CHAR);
-                    }
-                    buffer.push(spaces);
-
-                    line_index += 1;
-                    current_char_index = 0;
-                    max_char_index = 0;
-
-                    buffer.push(format!(
-                        "{}",
-                        cursor::Goto(x, y + line_index as u16 - scroll)
-                    ));
-
-                    last_line_index = line_index;
-                }
-
-                '\r' => {
-                    current_char_index = 0;
-                    buffer.push(format!(
-                        "{}",
-                        cursor::Goto(x, y + line_index as u16 - scroll)
-                    ));
-
-                    last_line_index = line_index;
-                }
-
-                _ => {
-                    // Emoji variation selectors have no length
-                    let is_variation_selector = c >= '\u{fe00}' && c <= '\u{fe0f}';
-
-                    if !is_variation_selector {
-                        current_char_index += UnicodeWidthChar::width(c).unwrap_or(0) as u16;
-                        max_char_index = std::cmp::max(max_char_index, current_char_index);
-                    }
-
-                    if current_char_index == w + 1 {
-                        line_index += 1;
-                        current_char_index = 1;
-                        max_char_index = 1;
+            if let Some(marker) = line.as_ref().and_then(|l| l.marker) {
+                buffer.push(marker.render((x + gutter, y + row), content_width));
+                continue;
+            }
 
-                        buffer.push(format!(
-                            "{}",
-                            cursor::Goto(x, y + line_index as u16 - scroll)
-                        ));
+            // Searches and selections are shown above the highlight rules
+            let mut highlights = index.map(|i| self.highlights(i)).unwrap_or_default();
+            if let Some(rules) = index.and_then(|i| rules.remove(&i)) {
+                highlights.extend(rules);
+            }
 
-                        last_line_index = line_index;
-                    }
+            buffer.push(Line::render(
+                line.as_deref(),
+                (x + gutter, y + row),
+                content_width,
+                self.hscroll,
+                &highlights,
+            ));
 
-                    buffer.push(format!("{}", c));
-                }
+            if let Some(line) = line.filter(|_| self.wrap == Wrap::Off) {
+                buffer.push(self.render_overflow(&line, (x + gutter, y + row), content_width));
             }
         }
 
-        if last_line_index as u16 - scroll <= h {
-            let mut spaces = format!(
-                "{}",
-                cursor::Goto(x + max_char_index, y + last_line_index as u16 - scroll)
-            );
-
-            for _ in max_char_index..w {
-                spaces.push(DELETE_CHAR);
-            }
-            buffer.push(spaces);
+        if let Some(cursor) = self.render_cursor() {
+            buffer.push(cursor);
         }
 
         // Render scrollbar,thanks @gdamms
-        // I have no idea what this code does, I copied/pasted it from gdamms, and then modified
-        // some stuff so that it would look right
-        if last_line_index > h {
+        if let Some((scroll_nb_top, bar_nb, scroll_nb_bottom)) = self.scrollbar() {
             let mut subbuffer = vec![];
             subbuffer.push(format!(
                 "{}{}{}{}",
@@ -565,23 +931,6 @@ impl Tile {
                 "▲"
             ));
 
-            let bar_portion = h as f32 / self.stdout.len() as f32;
-            let bar_nb = f32::max(1.0, (bar_portion * (h) as f32).round()) as u16;
-            let max_scroll = self.stdout.len() as isize - h as isize - 1;
-
-            let (scroll_nb_bottom, scroll_nb_top) = if self.scroll > max_scroll / 2 {
-                let scroll_nb_bottom = (self.stdout.len() as isize - self.scroll) as u16 - h;
-                let scroll_nb_bottom = scroll_nb_bottom as f32 / self.stdout.len() as f32;
-                let scroll_nb_bottom = (scroll_nb_bottom * (h as f32)).ceil() as u16;
-                let scroll_nb_top = h - bar_nb - scroll_nb_bottom;
-                (scroll_nb_bottom, scroll_nb_top)
-            } else {
-                let scroll_nb_top = self.scroll as f32 / self.stdout.len() as f32;
-                let scroll_nb_top = (scroll_nb_top * (h) as f32).ceil() as u16;
-                let scroll_nb_bottom = h - bar_nb - scroll_nb_top;
-                (scroll_nb_bottom, scroll_nb_top)
-            };
-
             for i in 1..=scroll_nb_top {
                 subbuffer.push(format!("{}{}", cursor::Goto(x + w + 1, y + i), "│"));
             }
@@ -601,18 +950,991 @@ impl Tile {
         buffer.join("")
     }
 
-    /// Returns the max scroll value.
-    pub fn max_scroll(&self) -> isize {
-        std::cmp::max(
-            0,
-            self.stdout.len() as isize - self.inner_size.1 as isize - 1,
+    /// Renders the timestamp of a line of the scrollback in the gutter, followed by a marker on
+    /// continuation lines when words are wrapped.
+    ///
+    /// Only the first line of a logical line has a timestamp, the others being its continuation.
+    fn render_gutter(&self, index: Option<usize>, (x, y): (u16, u16)) -> String {
+        let gutter = self.timestamps.width();
+
+        let continuation = match index {
+            Some(i) if self.wrap == Wrap::Word && self.is_continuation(i) => "↪",
+            _ if self.wrap == Wrap::Word => " ",
+            _ => "",
+        };
+
+        let timestamp = index
+            .filter(|i| !self.is_continuation(*i))
+            .and_then(|i| self.stdout.get(i))
+            .map(|l| self.format_timestamp(&l))
+            .unwrap_or_default();
+
+        format!(
+            "{}{}{}{:<width$}{}{}",
+            cursor::Goto(x, y),
+            style::Reset,
+            style::Faint,
+            timestamp,
+            continuation,
+            style::Reset,
+            width = gutter as usize,
         )
     }
 
-    /// Scrolls up one line.
-    pub fn scroll_up(&mut self, step: isize) {
-        self.sticky = false;
-        self.scroll = std::cmp::max(0, self.scroll - step);
+    /// Returns whether a line of the scrollback continues the line before it, which was wrapped.
+    pub fn is_continuation(&self, index: usize) -> bool {
+        index > 0
+            && self
+                .stdout
+                .get(index - 1)
+                .map(|l| !l.is_terminated())
+                .unwrap_or(false)
+    }
+
+    /// Formats the timestamp of a line as it is shown in the gutter, which is empty if the
+    /// timestamps are hidden or if the line has no timestamp.
+    pub fn format_timestamp(&self, line: &Line) -> String {
+        line.timestamp
+            .map(|t| self.timestamps.format(t, self.started.unwrap_or(t)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of rows of the scrollbar above its thumb, in its thumb and below its
+    /// thumb, or `None` if everything fits in the tile and there is no scrollbar.
+    ///
+    /// The rows are counted from the row below the ▲ arrow.
+    pub fn scrollbar(&self) -> Option<(u16, u16, u16)> {
+        let (_, h) = self.inner_size;
+        let len = self.view_len();
+
+        if len <= h as usize + 1 {
+            return None;
+        }
+
+        // I have no idea what this code does, I copied/pasted it from gdamms, and then modified
+        // some stuff so that it would look right
+        let bar_portion = h as f32 / len as f32;
+        let bar_nb = f32::max(1.0, (bar_portion * (h) as f32).round()) as u16;
+        let max_scroll = len as isize - h as isize - 1;
+
+        let (scroll_nb_bottom, scroll_nb_top) = if self.scroll > max_scroll / 2 {
+            let scroll_nb_bottom = ((len as isize - self.scroll) as u16).saturating_sub(h);
+            let scroll_nb_bottom = scroll_nb_bottom as f32 / len as f32;
+            let scroll_nb_bottom = (scroll_nb_bottom * (h as f32)).ceil() as u16;
+            let scroll_nb_top = h.saturating_sub(bar_nb + scroll_nb_bottom);
+            (scroll_nb_bottom, scroll_nb_top)
+        } else {
+            let scroll_nb_top = self.scroll as f32 / len as f32;
+            let scroll_nb_top = (scroll_nb_top * (h) as f32).ceil() as u16;
+            let scroll_nb_bottom = h.saturating_sub(bar_nb + scroll_nb_top);
+            (scroll_nb_bottom, scroll_nb_top)
+        };
+
+        Some((scroll_nb_top, bar_nb, scroll_nb_bottom))
+    }
+
+    /// Returns whether a point of the screen is on the scrollbar of the tile.
+    pub fn is_on_scrollbar(&self, (x, y): (u16, u16)) -> bool {
+        let (x0, y0) = self.inner_position;
+        let (w, h) = self.inner_size;
+        x == x0 + w + 1 && y0 <= y && y <= y0 + h && self.scrollbar().is_some()
+    }
+
+    /// Treats a click on the scrollbar: the arrows scroll by one line, the track scrolls by one
+    /// page, and the thumb can be dragged.
+    pub fn click_scrollbar(&mut self, (_, y): (u16, u16)) {
+        let (_, y0) = self.inner_position;
+        let (_, h) = self.inner_size;
+
+        let (top, bar, _) = match self.scrollbar() {
+            Some(scrollbar) => scrollbar,
+            None => return,
+        };
+
+        let row = y - y0;
+
+        if row == 0 {
+            self.scroll_up(1);
+        } else if row == h {
+            self.scroll_down(1);
+        } else if row <= top {
+            self.scroll_up(self.page_size());
+        } else if row > top + bar {
+            self.scroll_down(self.page_size());
+        } else {
+            self.scrollbar_grab = Some(row - top - 1);
+        }
+    }
+
+    /// Scrolls so that the thumb of the scrollbar follows the pointer while it is dragged.
+    pub fn drag_scrollbar(&mut self, (_, y): (u16, u16)) {
+        let (_, y0) = self.inner_position;
+        let (_, h) = self.inner_size;
+
+        let (grab, (_, bar, _)) = match (self.scrollbar_grab, self.scrollbar()) {
+            (Some(grab), Some(scrollbar)) => (grab, scrollbar),
+            _ => return,
+        };
+
+        // The thumb moves between the arrows
+        let track = h.saturating_sub(1 + bar);
+        if track == 0 {
+            return;
+        }
+
+        let top = (y as isize - y0 as isize - 1 - grab as isize).clamp(0, track as isize);
+        let max_scroll = self.max_scroll();
+
+        self.scroll = (top as f32 / track as f32 * max_scroll as f32).round() as isize;
+        self.sticky = self.scroll == max_scroll;
+    }
+
+    /// Renders the cursor of the copy mode, if it is visible.
+    pub fn render_cursor(&self) -> Option<String> {
+        let (x, y) = self.inner_position;
+        let (line, column) = self.selection?.cursor;
+        let column = column
+            .checked_sub(self.hscroll)
+            .filter(|c| *c < self.content_width())?;
+
+        let index = line.checked_sub(self.stdout.evicted())?;
+        let row = self.view_position(index) as isize - self.scroll;
+
+        if row < 0
+            || row > self.inner_size.1 as isize
+            || self.view_line(row as usize + self.scroll as usize) != Some(index)
+        {
+            return None;
+        }
+
+        // The cursor is shown even on columns that were never written
+        let grapheme = self
+            .stdout
+            .get(index)
+            .and_then(|l| {
+                l.cells()
+                    .into_iter()
+                    .find(|(c, _)| *c == column + self.hscroll)
+            })
+            .map(|(_, g)| g)
+            .unwrap_or_else(|| String::from(" "));
+
+        let style = Style {
+            fg: Some(Color::Indexed(0)),
+            bg: Some(Color::Indexed(6)),
+            ..Style::default()
+        };
+
+        Some(format!(
+            "{}{}{}{}",
+            cursor::Goto(x + self.gutter_width() + column, y + row as u16),
+            style.sgr(),
+            grapheme,
+            style::Reset,
+        ))
+    }
+
+    /// Returns the parts of a line of the scrollback that are highlighted.
+    pub fn highlights(&self, index: usize) -> Vec<Highlight> {
+        let mut highlights = vec![];
+
+        if let Some(selection) = &self.selection {
+            if let Some((start, end)) = selection.columns(index + self.stdout.evicted()) {
+                highlights.push(Highlight {
+                    start,
+                    end,
+                    style: Style {
+                        reverse: true,
+                        ..Style::default()
+                    },
+                });
+            }
+        }
+
+        if let Some(search) = &self.search {
+            for m in search.matches_on(index + self.stdout.evicted()) {
+                let background = if search.current == Some(*m) {
+                    Color::Indexed(208)
+                } else {
+                    Color::Indexed(3)
+                };
+
+                highlights.push(Highlight {
+                    start: m.start,
+                    end: m.end,
+                    style: Style {
+                        fg: Some(Color::Indexed(0)),
+                        bg: Some(background),
+                        ..Style::default()
+                    },
+                });
+            }
+        }
+
+        highlights
+    }
+
+    /// Returns the parts of lines of the scrollback that are highlighted by the highlight rules.
+    ///
+    /// The rules are matched on logical lines, so that text that was wrapped is still highlighted.
+    fn rule_highlights(&self, indices: &[Option<usize>]) -> HashMap<usize, Vec<Highlight>> {
+        let mut highlights: HashMap<usize, Vec<Highlight>> = HashMap::new();
+
+        if self.options.highlights.is_empty() {
+            return highlights;
+        }
+
+        // The index after the last logical line that was matched
+        let mut end = 0;
+
+        for &index in indices.iter().flatten() {
+            if index < end {
+                continue;
+            }
+
+            let (logical, next) = self.logical_line(self.logical_start(index));
+            end = next;
+
+            for rule in &self.options.highlights {
+                for m in logical.find(&rule.regex, 0) {
+                    highlights.entry(m.line).or_default().push(Highlight {
+                        start: m.start,
+                        end: m.end,
+                        style: rule.style,
+                    });
+                }
+            }
+        }
+
+        highlights
+    }
+
+    /// Returns the index of the first line of the scrollback of the logical line containing a
+    /// line.
+    pub fn logical_start(&self, index: usize) -> usize {
+        let mut start = index;
+
+        while start > 0 {
+            match self.stdout.get(start - 1) {
+                Some(line) if !line.is_terminated() => start -= 1,
+                _ => break,
+            }
+        }
+
+        start
+    }
+
+    /// Returns the logical line starting at a line of the scrollback, and the index of the line
+    /// after its end.
+    pub fn logical_line(&self, start: usize) -> (LogicalLine, usize) {
+        let mut logical = LogicalLine::new();
+        let mut index = start;
+
+        while let Some(line) = self.stdout.get(index) {
+            logical.push(index, &line);
+            index += 1;
+
+            if line.is_terminated() {
+                break;
+            }
+        }
+
+        (logical, index)
+    }
+
+    /// Starts a new search, or stops the current search.
+    pub fn set_search(&mut self, search: Option<Search>) {
+        self.search = search;
+        self.update_search();
+    }
+
+    /// Searches the output that arrived since the last update of the search.
+    fn update_search(&mut self) {
+        let mut search = match self.search.take() {
+            Some(search) => search,
+            None => return,
+        };
+
+        let offset = self.stdout.evicted();
+        let scanned = search.scanned;
+
+        search
+            .matches
+            .retain(|m| m.line >= offset && m.line < scanned);
+
+        search.scanned = self.scan(scanned, |_, _, logical| {
+            search.matches.extend(logical.find(&search.regex, offset))
+        });
+
+        self.search = Some(search);
+    }
+
+    /// Starts filtering the output, or stops filtering it.
+    ///
+    /// The first line shown stays the same, so that the user doesn't lose their position.
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        let first = self.view_line(self.scroll as usize).unwrap_or(0);
+
+        self.filter = filter;
+        self.update_filter();
+
+        // The folded sections are still shown as a single row
+        let position = self.view_position(first);
+
+        self.scroll = if self.sticky {
+            self.max_scroll()
+        } else {
+            std::cmp::min(position as isize, self.max_scroll())
+        };
+    }
+
+    /// Filters the output that arrived since the last update of the filter.
+    fn update_filter(&mut self) {
+        let mut filter = match self.filter.take() {
+            Some(filter) => filter,
+            None => return,
+        };
+
+        let offset = self.stdout.evicted();
+        let scanned = filter.scanned;
+
+        // The lines that were dropped from the scrollback disappear from the top of the view
+        let dropped = filter.position(offset);
+        filter.lines.drain(0..dropped);
+        filter.lines.retain(|l| *l < scanned);
+        self.scroll = std::cmp::max(0, self.scroll - dropped as isize);
+
+        filter.scanned = self.scan(scanned, |start, end, logical| {
+            if filter.regex.is_match(&logical.text) {
+                filter.lines.extend(start + offset..end + offset);
+            }
+        });
+
+        self.filter = Some(filter);
+    }
+
+    /// Counts the errors and the warnings in the output that arrived since the last update of
+    /// the counters.
+    fn update_counters(&mut self) {
+        let offset = self.stdout.evicted();
+
+        let mut errors = self.errors.take();
+        let mut warnings = self.warnings.take();
+
+        for counter in [&mut errors, &mut warnings].into_iter().flatten() {
+            let scanned = counter.scanned;
+
+            // The lines dropped from the scrollback are still counted
+            let dropped = counter.lines.partition_point(|l| *l < offset);
+            counter.lines.drain(0..dropped);
+            counter.dropped += dropped;
+            counter.lines.retain(|l| *l < scanned);
+
+            counter.scanned = self.scan(scanned, |start, _, logical| {
+                let timestamp = self.stdout.get(start).and_then(|l| l.timestamp);
+                if counter.matches(logical, timestamp) {
+                    counter.lines.push(start + offset);
+                }
+            });
+        }
+
+        self.errors = errors;
+        self.warnings = warnings;
+    }
+
+    /// Calls `f` on each logical line from the one containing the line `scanned`, with the indices
+    /// in the scrollback of its first line and of the line after its last line.
+    ///
+    /// Returns the absolute index of the first line of the last logical line, which is not
+    /// complete and will need to be scanned again.
+    fn scan<F>(&self, scanned: usize, mut f: F) -> usize
+    where
+        F: FnMut(usize, usize, &LogicalLine),
+    {
+        let offset = self.stdout.evicted();
+        let mut start = self.logical_start(scanned.saturating_sub(offset));
+
+        loop {
+            let (logical, end) = self.logical_line(start);
+            f(start, end, &logical);
+
+            // The last logical line is not complete
+            if end >= self.stdout.len() {
+                return start + offset;
+            }
+
+            start = end;
+        }
+    }
+
+    /// Returns the number of rows shown in the tile, i.e. the lines matching the filter if any,
+    /// and one summary row for each folded section.
+    pub fn view_len(&self) -> usize {
+        if self.is_folded() {
+            return self
+                .segments()
+                .into_iter()
+                .map(|(start, end, folded)| match folded {
+                    Some(_) => 1,
+                    None => self.visible_lines(start, end).1,
+                })
+                .sum();
+        }
+
+        match &self.filter {
+            Some(filter) => filter.lines.len(),
+            None => self.stdout.len(),
+        }
+    }
+
+    /// Returns what is shown on a row of the tile.
+    pub fn view_row(&self, position: usize) -> Option<Row> {
+        let offset = self.stdout.evicted();
+
+        if !self.is_folded() {
+            return match &self.filter {
+                Some(filter) => filter.lines.get(position).map(|l| Row::Line(l - offset)),
+                None => Some(position)
+                    .filter(|p| *p < self.stdout.len())
+                    .map(Row::Line),
+            };
+        }
+
+        let mut position = position;
+        for (start, end, folded) in self.segments() {
+            if let Some(section) = folded {
+                if position == 0 {
+                    return Some(Row::Summary(section));
+                }
+                position -= 1;
+                continue;
+            }
+
+            let (first, count) = self.visible_lines(start, end);
+            if position < count {
+                let line = match &self.filter {
+                    Some(filter) => filter.lines[first + position],
+                    None => first + position,
+                };
+                return Some(Row::Line(line - offset));
+            }
+            position -= count;
+        }
+
+        None
+    }
+
+    /// Returns the index in the scrollback of a line shown in the tile, which is the first line
+    /// of the section for the summary of a folded section.
+    pub fn view_line(&self, position: usize) -> Option<usize> {
+        self.view_row(position).map(|row| match row {
+            Row::Line(index) => index,
+            Row::Summary(section) => self.section_start(section) - self.stdout.evicted(),
+        })
+    }
+
+    /// Returns where a line of the scrollback is shown in the tile, or where it would be if it is
+    /// hidden by the filter or by a folded section.
+    pub fn view_position(&self, index: usize) -> usize {
+        let offset = self.stdout.evicted();
+
+        if !self.is_folded() {
+            return match &self.filter {
+                Some(filter) => filter.position(index + offset),
+                None => index,
+            };
+        }
+
+        let line = index + offset;
+        let mut position = 0;
+        for (start, end, folded) in self.segments() {
+            let shown = match folded {
+                Some(_) => 1,
+                None => self.visible_lines(start, end).1,
+            };
+
+            if line < end {
+                return position
+                    + match (folded, &self.filter) {
+                        (Some(_), _) => 0,
+                        (None, Some(filter)) => filter.position(line) - filter.position(start),
+                        (None, None) => line - start,
+                    };
+            }
+
+            position += shown;
+        }
+
+        position
+    }
+
+    /// Jumps to the next or the previous match of the search.
+    pub fn search_next(&mut self, forward: bool) {
+        let offset = self.stdout.evicted();
+        let from = self.view_line(self.scroll as usize).unwrap_or(0) + offset;
+
+        let next = match self.search.as_mut() {
+            Some(search) => {
+                search.current = search.next(from, forward);
+                search.current
+            }
+            None => None,
+        };
+
+        if let Some(next) = next {
+            let index = next.line.saturating_sub(offset);
+
+            // The match would be hidden by its folded section
+            if let Some(section) = self
+                .section_of(index)
+                .and_then(|s| self.sections.get_mut(s))
+            {
+                section.folded = false;
+            }
+
+            self.scroll_to(index);
+        }
+    }
+
+    /// Scrolls so that a line is visible, in the middle of the tile if possible.
+    pub fn scroll_to(&mut self, index: usize) {
+        let (_, h) = self.inner_size;
+        let target = self.view_position(index) as isize - h as isize / 2;
+        let max_scroll = self.max_scroll();
+        self.scroll = target.clamp(0, max_scroll);
+        self.sticky = self.scroll == max_scroll;
+    }
+
+    /// Returns the status of the tile shown on its border, e.g. the number of matches of a
+    /// search.
+    pub fn status(&self) -> String {
+        let mut status = vec![];
+
+        if let Some(notice) = &self.notice {
+            status.push(format!("[{}]", notice));
+        }
+
+        if self.selection.is_some() {
+            status.push(String::from("[COPY]"));
+        }
+
+        match self.link {
+            Some(LinkMode::Offset) => status.push(String::from("[link]")),
+            Some(LinkMode::Time) => status.push(String::from("[link: time]")),
+            None => (),
+        }
+
+        for (counter, symbol) in [(&self.errors, '✖'), (&self.warnings, '⚠')] {
+            if let Some(count) = counter.as_ref().map(|x| x.count()).filter(|x| *x > 0) {
+                status.push(format!("{} {}", symbol, count));
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            status.push(format!(
+                "[& {}]",
+                utils::truncate(filter.regex.as_str(), MAX_FILTER_LEN)
+            ));
+        }
+
+        if let Some(search) = &self.search {
+            status.push(match search.current_index() {
+                Some(i) => format!("[{}/{}]", i + 1, search.matches.len()),
+                None => format!("[{}]", search.matches.len()),
+            });
+        }
+
+        status.join(" ")
+    }
+
+    /// Enters copy mode, with the cursor at the beginning of the last visible line.
+    pub fn enter_copy_mode(&mut self) {
+        let last = std::cmp::min(
+            self.scroll as usize + self.inner_size.1 as usize,
+            self.view_len().saturating_sub(1),
+        );
+        let line = self.view_line(last).unwrap_or(0) + self.stdout.evicted();
+        self.selection = Some(Selection::new((line, 0)));
+    }
+
+    /// Leaves copy mode.
+    pub fn exit_copy_mode(&mut self) {
+        self.selection = None;
+    }
+
+    /// Starts or stops selecting text at the cursor.
+    pub fn toggle_selection(&mut self) {
+        if let Some(selection) = self.selection.as_mut() {
+            selection.anchor = match selection.anchor {
+                Some(_) => None,
+                None => Some(selection.cursor),
+            };
+        }
+    }
+
+    /// Moves the cursor of the copy mode, scrolling if needed.
+    pub fn move_cursor(&mut self, lines: isize, columns: isize) {
+        let (line, column) = match self.selection {
+            Some(selection) => selection.cursor,
+            None => return,
+        };
+
+        let offset = self.stdout.evicted();
+        let position = self.view_position(line.saturating_sub(offset)) as isize + lines;
+        let position = position.clamp(0, self.view_len().saturating_sub(1) as isize);
+        let column = (column as isize + columns).clamp(0, self.wrap_width() as isize - 1);
+
+        self.set_cursor(position as usize, column as u16);
+    }
+
+    /// Moves the cursor of the copy mode to the beginning or the end of its line.
+    pub fn move_cursor_to_edge(&mut self, end: bool) {
+        let (line, _) = match self.selection {
+            Some(selection) => selection.cursor,
+            None => return,
+        };
+
+        let column = if end {
+            line.checked_sub(self.stdout.evicted())
+                .and_then(|i| self.stdout.get(i))
+                .map(|l| utils::text_width(l.text().trim_end()))
+                .unwrap_or(0)
+                .saturating_sub(1)
+        } else {
+            0
+        };
+
+        if let Some(selection) = self.selection.as_mut() {
+            selection.cursor.1 = column;
+        }
+
+        self.show_column(column);
+    }
+
+    /// Moves the cursor of the copy mode to a position of the view, and scrolls so that it is
+    /// visible.
+    fn set_cursor(&mut self, position: usize, column: u16) {
+        let line = match self.view_line(position) {
+            Some(index) => index + self.stdout.evicted(),
+            None => return,
+        };
+
+        if let Some(selection) = self.selection.as_mut() {
+            selection.cursor = (line, column);
+        }
+
+        self.show_column(column);
+
+        let h = self.inner_size.1 as isize;
+        let position = position as isize;
+
+        if position < self.scroll {
+            self.scroll_up(self.scroll - position);
+        } else if position > self.scroll + h {
+            self.scroll_down(position - self.scroll - h);
+        }
+    }
+
+    /// Returns the position in the output under a point of the screen, the point being clamped to
+    /// the content of the tile.
+    pub fn position_at(&self, (x, y): (u16, u16)) -> Option<Position> {
+        let (x0, y0) = self.inner_position;
+        let (_, h) = self.inner_size;
+
+        let row = y.saturating_sub(y0).min(h) as usize;
+        let column = x
+            .saturating_sub(x0 + self.gutter_width())
+            .min(self.content_width() - 1)
+            + self.hscroll;
+
+        let last = self.view_len().checked_sub(1)?;
+        let position = std::cmp::min(self.scroll as usize + row, last);
+
+        Some((self.view_line(position)? + self.stdout.evicted(), column))
+    }
+
+    /// Returns whether a point of the screen is on the content of the tile.
+    pub fn is_on_content(&self, (x, y): (u16, u16)) -> bool {
+        let (x0, y0) = self.inner_position;
+        let (w, h) = self.inner_size;
+        x0 <= x && x < x0 + w && y0 <= y && y <= y0 + h
+    }
+
+    /// Starts selecting text with the mouse.
+    pub fn start_drag(&mut self, point: (u16, u16)) {
+        if let Some(position) = self.position_at(point) {
+            self.selection = Some(Selection {
+                anchor: Some(position),
+                cursor: position,
+                mouse: true,
+            });
+        }
+    }
+
+    /// Extends the selection to a point of the screen, scrolling if the point is above or below
+    /// the content.
+    pub fn drag(&mut self, (x, y): (u16, u16)) {
+        let (_, y0) = self.inner_position;
+        let (_, h) = self.inner_size;
+
+        if y < y0 {
+            self.scroll_up(1);
+        } else if y > y0 + h {
+            self.scroll_down(1);
+        }
+
+        let position = self.position_at((x, y));
+
+        if let (Some(selection), Some(position)) = (self.selection.as_mut(), position) {
+            selection.cursor = position;
+        }
+    }
+
+    /// Returns the selected text, without escape sequences.
+    ///
+    /// Lines that were wrapped are joined, so that the text is the same as the command wrote.
+    pub fn selected_text(&self) -> Option<String> {
+        let selection = self.selection?;
+        let ((first, _), (last, _)) = selection.range()?;
+
+        let offset = self.stdout.evicted();
+        let first = self.view_position(first.saturating_sub(offset));
+        let last = self.view_position(last.saturating_sub(offset));
+
+        let mut text = String::new();
+
+        for position in first..=last {
+            let index = match self.view_line(position) {
+                Some(index) => index,
+                None => break,
+            };
+
+            let line = match self.stdout.get(index) {
+                Some(line) => line,
+                None => break,
+            };
+
+            let (start, end) = match selection.columns(index + offset) {
+                Some(columns) => columns,
+                None => continue,
+            };
+
+            text.push_str(&line.slice(start, end));
+
+            // Lines hidden by the filter are not copied
+            let next = self.view_line(position + 1);
+            if position < last && (line.is_terminated() || next != Some(index + 1)) {
+                let len = text.trim_end_matches(' ').len();
+                text.truncate(len);
+                text.push('\n');
+            }
+        }
+
+        Some(text)
+    }
+
+    /// Clears the bell and activity flags, once the user has seen the tile.
+    pub fn clear_notifications(&mut self) {
+        self.bell = false;
+        self.activity = false;
+    }
+
+    /// Returns the max scroll value.
+    pub fn max_scroll(&self) -> isize {
+        std::cmp::max(0, self.view_len() as isize - self.inner_size.1 as isize - 1)
+    }
+
+    /// Returns the number of columns where the output of the command is written, i.e. the width
+    /// of the tile without the gutter.
+    pub fn content_width(&self) -> u16 {
+        self.inner_size.0.saturating_sub(self.gutter_width()).max(1)
+    }
+
+    /// Returns the number of columns of the gutter, with the timestamps and the continuation
+    /// markers of the word wrap.
+    ///
+    /// The gutter is hidden when the tile is too narrow to show content next to it.
+    pub fn gutter_width(&self) -> u16 {
+        let gutter = self.timestamps.width() + (self.wrap == Wrap::Word) as u16;
+        if gutter < self.inner_size.0 {
+            gutter
+        } else {
+            0
+        }
+    }
+
+    /// Cycles between the ways of showing the timestamps of the lines.
+    pub fn cycle_timestamps(&mut self) {
+        self.timestamps = self.timestamps.next();
+
+        // The gutter changes the width of the content
+        self.reflow();
+    }
+
+    /// Returns the width at which the lines are wrapped.
+    pub fn wrap_width(&self) -> u16 {
+        match self.wrap {
+            Wrap::Hard | Wrap::Word => self.content_width(),
+            Wrap::Off => UNWRAPPED_WIDTH,
+        }
+    }
+
+    /// Switches to the next way of showing the lines wider than the tile.
+    pub fn cycle_wrap(&mut self) {
+        self.wrap = self.wrap.next();
+        self.hscroll = 0;
+        self.reflow();
+    }
+
+    /// Returns the number of columns that can be scrolled horizontally, so that the end of the
+    /// widest line shown is at the right edge of the tile.
+    pub fn max_hscroll(&self) -> u16 {
+        let width = (0..=self.inner_size.1)
+            .filter_map(|row| self.view_line(self.scroll as usize + row as usize))
+            .filter_map(|index| self.stdout.get(index))
+            .map(|line| line.width())
+            .max()
+            .unwrap_or(0);
+
+        width.saturating_sub(self.content_width())
+    }
+
+    /// Scrolls horizontally, to the left if `step` is negative, when wrapping is off.
+    pub fn scroll_horizontally(&mut self, step: isize) {
+        if self.wrap != Wrap::Off {
+            return;
+        }
+
+        let hscroll = self.hscroll as isize + step;
+        self.hscroll = hscroll.clamp(0, self.max_hscroll() as isize) as u16;
+    }
+
+    /// Scrolls horizontally so that a column is visible.
+    fn show_column(&mut self, column: u16) {
+        let w = self.content_width();
+
+        if column < self.hscroll {
+            self.hscroll = column;
+        } else if column >= self.hscroll + w {
+            self.hscroll = column + 1 - w;
+        }
+    }
+
+    /// Renders the markers showing that a line continues beyond the edges of the tile, when
+    /// wrapping is off.
+    fn render_overflow(&self, line: &Line, (x, y): (u16, u16), w: u16) -> String {
+        let width = line.width();
+        let mut buffer = format!("{}{}", style::Reset, style::Bold);
+
+        if self.hscroll > 0 && width > 0 {
+            buffer.push_str(&format!("{}‹", cursor::Goto(x, y)));
+        }
+
+        if width > self.hscroll + w {
+            buffer.push_str(&format!("{}›", cursor::Goto(x + w - 1, y)));
+        }
+
+        buffer.push_str(&format!("{}", style::Reset));
+        buffer
+    }
+
+    /// Returns the number of lines scrolled by a page, i.e. the number of visible lines.
+    pub fn page_size(&self) -> isize {
+        self.inner_size.1 as isize + 1
+    }
+
+    /// Scrolls so that a line of output is at the top of the tile, lines being numbered from 1
+    /// like in the output of the command, i.e. before they are wrapped.
+    pub fn go_to_line(&mut self, number: usize) {
+        let mut index = 0;
+        let mut current = 1;
+
+        while current < number && index < self.stdout.len() {
+            if let Some(line) = self.stdout.get(index) {
+                if line.is_terminated() {
+                    current += 1;
+                }
+            }
+            index += 1;
+        }
+
+        self.scroll_to_top(index);
+    }
+
+    /// Scrolls so that a line of the scrollback is at the top of the tile, if possible.
+    pub fn scroll_to_top(&mut self, index: usize) {
+        let max_scroll = self.max_scroll();
+        self.scroll = std::cmp::min(self.view_position(index) as isize, max_scroll);
+        self.sticky = self.scroll == max_scroll;
+    }
+
+    /// Returns the time when the first line shown in the tile arrived.
+    pub fn top_timestamp(&self) -> Option<SystemTime> {
+        self.view_line(self.scroll as usize)
+            .and_then(|i| self.stdout.get(i))
+            .and_then(|l| l.timestamp)
+    }
+
+    /// Scrolls so that the first line shown is the first line that arrived at or after a time.
+    pub fn scroll_to_time(&mut self, time: SystemTime) {
+        // The lines arrive in order, so their timestamps are sorted
+        let (mut low, mut high) = (0, self.view_len());
+
+        while low < high {
+            let middle = (low + high) / 2;
+
+            let before = self
+                .view_line(middle)
+                .and_then(|i| self.stdout.get(i))
+                .and_then(|l| l.timestamp)
+                .map(|t| t < time)
+                .unwrap_or(false);
+
+            if before {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        let max_scroll = self.max_scroll();
+        self.scroll = std::cmp::min(low as isize, max_scroll);
+        self.sticky = self.scroll == max_scroll;
+    }
+
+    /// Scrolls to the next or the previous error or warning.
+    pub fn jump_to_counted(&mut self, forward: bool) {
+        let offset = self.stdout.evicted();
+
+        let mut lines = [&self.errors, &self.warnings]
+            .into_iter()
+            .flatten()
+            .flat_map(|x| x.lines.iter())
+            .map(|x| x - offset)
+            .collect::<Vec<_>>();
+
+        lines.sort_unstable();
+
+        let scroll = self.scroll as usize;
+
+        let target = if forward {
+            lines.into_iter().find(|x| self.view_position(*x) > scroll)
+        } else {
+            lines
+                .into_iter()
+                .rev()
+                .find(|x| self.view_position(*x) < scroll)
+        };
+
+        if let Some(target) = target {
+            self.scroll_to_top(target);
+        }
+    }
+
+    /// Scrolls up one line.
+    pub fn scroll_up(&mut self, step: isize) {
+        self.sticky = false;
+        self.scroll = std::cmp::max(0, self.scroll - step);
     }
 
     /// Scrolls down one line.
@@ -644,6 +1966,18 @@ impl Tile {
     /// Restarts the child command.
     pub fn restart(&mut self) {
         self.kill();
+
+        // The killed run ends here, even if its finish line arrives after the output of the next
+        // run, and only the next run stays unfolded
+        if self.running > 0 {
+            self.add_finish_line(None);
+        }
+        self.fold_all(true);
+
+        for counter in [&mut self.errors, &mut self.warnings].into_iter().flatten() {
+            counter.reset();
+        }
+
         self.start();
     }
 
@@ -657,54 +1991,551 @@ impl Tile {
     pub fn resize(&mut self, (w, h): (u16, u16)) {
         self.outer_size = (w, h);
         self.inner_size = (w - 4, h - 5);
+        self.reflow();
+    }
+
+    /// Wraps the content again, after the width of the content changed.
+    pub fn reflow(&mut self) {
+        let (w, h) = (self.content_width(), self.inner_size.1);
 
         if let Some(pty) = self.pty.as_mut() {
-            pty.resize(pty_process::Size::new(self.inner_size.1, self.inner_size.0))
-                .unwrap();
+            pty.resize(pty_process::Size::new(h, w)).unwrap();
         }
 
-        let old_stdout = std::mem::replace(&mut self.stdout, vec![String::new()]);
-        for s in old_stdout {
-            self.push_stdout(s);
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.resize((w, h));
+        }
+
+        // The positions of the selection would not be valid anymore
+        self.selection = None;
+
+        // Push the content again from the beginning so that it wraps at the new width
+        let empty = self.stdout.empty_clone();
+        let old_stdout = std::mem::replace(&mut self.stdout, empty);
+        let parser = std::mem::take(&mut self.parser);
+
+        // The first line starts with the style of the lines that were dropped
+        self.style = old_stdout.get(0).map(|l| l.style).unwrap_or_default();
+        self.last_line_mut().style = self.style;
+        self.column_number = 0;
+        self.grapheme.clear();
+        self.word_break = None;
+
+        // The search and the filter are updated once everything is pushed
+        let search = self.search.take();
+        let filter = self.filter.take();
+
+        // The sections are found again from the markers, and stay folded
+        let folded = std::mem::take(&mut self.sections)
+            .into_iter()
+            .map(|s| s.folded)
+            .collect::<Vec<_>>();
+
+        // The lines keep the time when they arrived
+        let now = SystemTime::now();
+        for (_, line) in old_stdout.iter() {
+            self.arrival = line.timestamp.unwrap_or(now);
+
+            match line.marker {
+                Some(marker) => {
+                    self.last_line_mut().marker = Some(marker);
+                    self.push("\n");
+                    self.close_section(marker);
+                }
+                None => self.push(&line.content),
+            }
+        }
+
+        for (section, folded) in self.sections.iter_mut().zip(folded) {
+            section.folded = folded;
+        }
+        self.update_sections();
+
+        self.parser = parser;
+
+        self.search = search;
+        if let Some(search) = self.search.as_mut() {
+            search.reset();
+        }
+        self.update_search();
+
+        self.filter = filter;
+        if let Some(filter) = self.filter.as_mut() {
+            filter.reset();
+        }
+        self.update_filter();
+
+        // The counters keep the lines that were dropped, and count the others again
+        for counter in [&mut self.errors, &mut self.warnings].into_iter().flatten() {
+            counter.rescan();
+        }
+        self.update_counters();
+
+        if self.sticky {
+            self.scroll = self.max_scroll();
         }
     }
 
-    /// Draws a line.
+    /// Ends the current section of the output with a separator.
     pub fn add_line(&mut self) {
-        let mut line = String::new();
-        for _ in 0..self.inner_size.0 - 1 {
-            line.push('─');
-        }
-
-        self.sender
-            .send(Msg::Stdout(
-                self.coords,
-                format!("\n{}{}\n", color::Reset.fg_str(), line),
-            ))
-            .unwrap();
-    }
-
-    /// Draws a finish line, green if success or red if failure.
-    pub fn add_finish_line(&mut self, success: bool) {
-        let mut line = String::new();
-        for _ in 0..self.inner_size.0 - 1 {
-            line.push('─');
-        }
-
-        self.sender
-            .send(Msg::Stdout(
-                self.coords,
-                format!(
-                    "\n{}{}{}\n",
-                    color::Reset.fg_str(),
-                    if success {
-                        color::Green.fg_str()
-                    } else {
-                        color::Red.fg_str()
-                    },
-                    line
-                ),
-            ))
-            .unwrap();
+        self.push_marker(Marker::Separator);
+    }
+
+    /// Ends the current run of the command with its exit status and a finish line, green if
+    /// success or red if failure.
+    pub fn add_finish_line(&mut self, code: Option<i32>) {
+        let marker = Marker::Finish(code);
+
+        self.push_stdout(format!(
+            "{}{}{}\r{}",
+            style::Bold,
+            marker.color(),
+            marker.message(),
+            style::Reset,
+        ));
+
+        self.push_marker(marker);
+    }
+
+    /// Ends the current section of the output with a marker line.
+    pub fn push_marker(&mut self, marker: Marker) {
+        self.arrival = SystemTime::now();
+
+        if !self.stdout.last().content.is_empty() {
+            self.push("\n");
+        }
+
+        self.last_line_mut().marker = Some(marker);
+        self.push("\n");
+        self.close_section(marker);
+
+        // Pushing the marker can drop lines from the scrollback
+        self.update_sections();
+
+        if let Marker::Finish(_) = marker {
+            self.running = self.running.saturating_sub(1);
+        }
+
+        self.update_search();
+        self.update_filter();
+        self.update_counters();
+
+        if self.sticky {
+            self.scroll = self.max_scroll();
+        }
+    }
+
+    /// Adds the section ended by the marker on the line before the last one.
+    fn close_section(&mut self, marker: Marker) {
+        let offset = self.stdout.evicted();
+        let end = offset + self.stdout.len() - 2;
+        let start = self.section_start(self.sections.len());
+
+        // The line before a finish line is the exit status, which is not part of the output
+        let last = match marker {
+            Marker::Separator => end,
+            Marker::Finish(_) => std::cmp::max(start, end.saturating_sub(1)),
+        };
+
+        let lines = (start..last)
+            .filter(|i| {
+                self.stdout
+                    .get(i - offset)
+                    .map(|l| l.is_terminated())
+                    .unwrap_or(false)
+            })
+            .count();
+
+        self.sections.push(Section {
+            end,
+            marker,
+            lines,
+            folded: false,
+        });
+    }
+
+    /// Returns the absolute index of the first line of a section, which is the index of the
+    /// section that is still being written if it is the number of sections.
+    fn section_start(&self, section: usize) -> usize {
+        let offset = self.stdout.evicted();
+        match section.checked_sub(1).and_then(|s| self.sections.get(s)) {
+            Some(previous) => std::cmp::max(previous.end + 1, offset),
+            None => offset,
+        }
+    }
+
+    /// Returns the index of the section containing a line of the scrollback, or `None` if the
+    /// line is in the section that is still being written.
+    fn section_of(&self, index: usize) -> Option<usize> {
+        let index = index + self.stdout.evicted();
+        let section = self.sections.partition_point(|s| s.end < index);
+        Some(section).filter(|s| *s < self.sections.len())
+    }
+
+    /// Returns whether a section of the tile is folded.
+    fn is_folded(&self) -> bool {
+        self.sections.iter().any(|s| s.folded)
+    }
+
+    /// Returns the parts of the scrollback shown in the tile, as the absolute indices of their
+    /// first line and of the line after their last line, with the index of their section if it
+    /// is folded.
+    fn segments(&self) -> Vec<(usize, usize, Option<usize>)> {
+        let mut segments = vec![];
+
+        // The sections whose lines were all dropped are not shown
+        let offset = self.stdout.evicted();
+
+        for (i, section) in self.sections.iter().enumerate() {
+            if section.end < offset {
+                continue;
+            }
+
+            let folded = Some(i).filter(|_| section.folded);
+            segments.push((self.section_start(i), section.end + 1, folded));
+        }
+
+        let end = self.stdout.evicted() + self.stdout.len();
+        segments.push((self.section_start(self.sections.len()), end, None));
+        segments
+    }
+
+    /// Returns where the lines between two absolute indices start in the lines shown by the
+    /// filter, or in the scrollback if there is no filter, and how many of them are shown.
+    fn visible_lines(&self, start: usize, end: usize) -> (usize, usize) {
+        match &self.filter {
+            Some(filter) => {
+                let first = filter.position(start);
+                (first, filter.position(end) - first)
+            }
+            None => (start, end - start),
+        }
+    }
+
+    /// Removes the sections whose lines were all dropped from the scrollback.
+    fn update_sections(&mut self) {
+        let offset = self.stdout.evicted();
+        let dropped = self.sections.partition_point(|s| s.end < offset);
+        self.sections.drain(..dropped);
+    }
+
+    /// Folds or unfolds a section, keeping its first line at the top of the tile.
+    pub fn toggle_section(&mut self, section: usize) {
+        let start = self.section_start(section) - self.stdout.evicted();
+
+        if let Some(section) = self.sections.get_mut(section) {
+            section.folded = !section.folded;
+        }
+
+        self.scroll_to_top(start);
+    }
+
+    /// Folds or unfolds the section at the top of the tile.
+    pub fn toggle_top_section(&mut self) {
+        let top = self.view_line(self.scroll as usize).unwrap_or(0);
+        if let Some(section) = self.section_of(top) {
+            self.toggle_section(section);
+        }
+    }
+
+    /// Folds all the sections if one of them is unfolded, unfolds them otherwise.
+    pub fn toggle_all_sections(&mut self) {
+        let top = self.view_line(self.scroll as usize).unwrap_or(0);
+        let fold = self.sections.iter().any(|s| !s.folded);
+        self.fold_all(fold);
+
+        // The tile keeps following the output, or stays where the user was
+        if !self.sticky {
+            self.scroll_to_top(top);
+        }
+    }
+
+    /// Folds or unfolds all the sections.
+    fn fold_all(&mut self, folded: bool) {
+        for section in &mut self.sections {
+            section.folded = folded;
+        }
+
+        if self.sticky {
+            self.scroll = self.max_scroll();
+        }
+    }
+
+    /// Returns the folded section whose summary is at a point of the terminal, if any.
+    pub fn summary_at(&self, (x, y): (u16, u16)) -> Option<usize> {
+        if !self.is_on_content((x, y)) {
+            return None;
+        }
+
+        let row = (y - self.inner_position.1) as usize;
+        match self.view_row(self.scroll as usize + row) {
+            Some(Row::Summary(section)) => Some(section),
+            _ => None,
+        }
+    }
+
+    /// Renders the summary of a folded section at the specified position, on `w` columns.
+    fn render_summary(&self, section: usize, (x, y): (u16, u16), w: u16) -> String {
+        let section = &self.sections[section];
+
+        let summary = section
+            .summary()
+            .chars()
+            .take(w as usize)
+            .collect::<String>();
+
+        format!(
+            "{}{}{}{}{:<width$}{}",
+            cursor::Goto(x, y),
+            style::Reset,
+            style::Bold,
+            section.marker.color(),
+            summary,
+            style::Reset,
+            width = w as usize,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use regex::Regex;
+
+    use crate::line::Item;
+
+    use super::*;
+
+    /// Returns a tile that isn't running a command, with the given options.
+    fn tile(options: TileOptions) -> Tile {
+        TileBuilder::new()
+            .command(vec![String::from("true")])
+            .options(options)
+            .coords((0, 0))
+            .position((1, 1))
+            .size((40, 12))
+            .sender(channel().0)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn narrow_tile_with_timestamps() {
+        for width in 5..20 {
+            let mut tile = tile(TileOptions::default());
+            tile.resize((width, 12));
+            tile.push_stdout(String::from("a line wider than the tile\n"));
+            tile.add_finish_line(Some(0));
+
+            for wrap in [Wrap::Hard, Wrap::Word] {
+                tile.wrap = wrap;
+                tile.timestamps = Timestamps::Absolute;
+                tile.reflow();
+
+                tile.render_content(true);
+                assert!(tile.gutter_width() < tile.inner_size.0);
+                assert!(tile.content_width() >= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn style_of_the_dropped_lines() {
+        let mut tile = tile(TileOptions {
+            scrollback_lines: Some(3),
+            ..TileOptions::default()
+        });
+
+        tile.push_stdout(String::from("\x1b[31mred\n"));
+        for i in 0..5 {
+            tile.push_stdout(format!("line {}\n", i));
+        }
+
+        let red = Some(Color::Indexed(1));
+        assert_eq!(tile.stdout.get(0).unwrap().style.fg, red);
+
+        tile.resize((30, 12));
+        assert_eq!(tile.stdout.get(0).unwrap().style.fg, red);
+        assert_eq!(tile.style.fg, red);
+    }
+
+    #[test]
+    fn filter_with_folded_sections() {
+        let mut tile = tile(TileOptions::default());
+
+        for run in 0..3 {
+            for i in 0..20 {
+                tile.push_stdout(format!("run {} line {}\n", run, i));
+            }
+            tile.add_finish_line(Some(0));
+        }
+        tile.toggle_all_sections();
+
+        for i in 0..40 {
+            tile.push_stdout(format!("line {}\n", i));
+        }
+
+        tile.scroll_up(20);
+        let (scroll, top) = (tile.scroll, tile.view_line(tile.scroll as usize));
+
+        tile.set_filter(Some(Filter::new(Regex::new("line").unwrap())));
+        assert_eq!(tile.view_line(tile.scroll as usize), top);
+
+        tile.set_filter(None);
+        assert_eq!(tile.scroll, scroll);
+        assert_eq!(tile.view_line(tile.scroll as usize), top);
+    }
+
+    #[test]
+    fn filter_with_dropped_lines() {
+        let mut tile = tile(TileOptions {
+            scrollback_lines: Some(10),
+            ..TileOptions::default()
+        });
+
+        let even = || Some(Filter::new(Regex::new("even").unwrap()));
+        tile.set_filter(even());
+
+        for i in 0..30 {
+            let parity = if i % 2 == 0 { "even" } else { "odd" };
+            tile.push_stdout(format!("{} {}\n", parity, i));
+
+            // The lines filtered as they arrive are the ones a new filter finds
+            let lines = tile.filter.as_ref().unwrap().lines.clone();
+            assert!(lines.iter().all(|l| *l >= tile.stdout.evicted()));
+
+            tile.set_filter(even());
+            assert_eq!(tile.filter.as_ref().unwrap().lines, lines);
+        }
+
+        // The rest of a wrapped line is still shown once its start is dropped
+        tile.push_stdout(format!("even {}\n", "a".repeat(60)));
+        for _ in 0..8 {
+            tile.push_stdout(String::from("odd\n"));
+        }
+
+        let offset = tile.stdout.evicted();
+        assert_eq!(tile.filter.as_ref().unwrap().lines, vec![offset]);
+        assert_eq!(tile.view_line(0), Some(0));
+        assert!(tile.stdout.get(0).unwrap().content.starts_with("aaa"));
+    }
+
+    #[test]
+    fn tab_at_the_right_edge() {
+        let mut tile = tile(TileOptions::default());
+        assert_eq!(tile.content_width(), 36);
+
+        // The tab stops at the edge of the tile, and the backspace moves back from there
+        tile.push_stdout(format!("{}\t\x08x\n", "a".repeat(34)));
+
+        let line = tile.stdout.get(0).unwrap();
+        assert_eq!(line.cells().last(), Some(&(35, String::from("x"))));
+        assert_eq!(line.text(), format!("{} x", "a".repeat(34)));
+
+        let rendered = Line::render(Some(&line), (1, 1), 36, 0, &[]);
+        assert!(rendered.contains('x'));
+
+        // Nothing is wrapped to the next row
+        assert_eq!(tile.stdout.get(1).unwrap().content, "");
+    }
+
+    #[test]
+    fn wide_graphemes_at_the_right_edge() {
+        let mut tile = tile(TileOptions::default());
+        let a = |n| "a".repeat(n);
+
+        tile.push_stdout(format!("{}中\n", a(35)));
+        tile.push_stdout(format!("{}👨\u{200d}👩\u{200d}👧\n", a(34)));
+        tile.push_stdout(format!("{}e\u{301}x\n", a(35)));
+        tile.push_stdout(format!("{}❤\u{fe0f}\n", a(35)));
+
+        // Each grapheme cluster stays on a single row, wrapped when it gets too wide
+        let lasts = (0..tile.stdout.len())
+            .map(|i| {
+                tile.stdout
+                    .get(i)
+                    .unwrap()
+                    .cells()
+                    .pop()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lasts,
+            vec![
+                (34, String::from("a")),
+                (0, String::from("中")),
+                (34, String::from("👨\u{200d}👩\u{200d}👧")),
+                (35, String::from("e\u{301}")),
+                (0, String::from("x")),
+                (34, String::from("a")),
+                (0, String::from("❤\u{fe0f}")),
+                (0, String::new()),
+            ]
+        );
+
+        // The rows fit in the tile once laid out
+        for i in 0..tile.stdout.len() {
+            let end = tile
+                .stdout
+                .get(i)
+                .unwrap()
+                .layout()
+                .iter()
+                .fold(0, |end, x| match x {
+                    Item::Grapheme { column, width, .. } => std::cmp::max(end, column + width),
+                    _ => end,
+                });
+            assert!(end <= tile.content_width());
+        }
+    }
+
+    #[test]
+    fn finish_line_at_the_start_of_the_scrollback() {
+        let mut tile = tile(TileOptions {
+            scrollback_lines: Some(4),
+            ..TileOptions::default()
+        });
+
+        tile.push_stdout(String::from("a\nb\n"));
+        tile.add_finish_line(Some(0));
+        tile.push_stdout(String::from("c\nd\n"));
+        tile.resize((30, 12));
+
+        assert_eq!(tile.sections.len(), 1);
+    }
+
+    #[test]
+    fn sections_dropped_from_the_scrollback() {
+        let mut tile = tile(TileOptions {
+            scrollback_lines: Some(4),
+            ..TileOptions::default()
+        });
+
+        tile.push_stdout(String::from("a\nb\n"));
+        tile.add_line();
+        tile.toggle_all_sections();
+
+        // The separators drop the folded section from the scrollback
+        for _ in 0..6 {
+            tile.add_line();
+        }
+
+        // Only the sections of the separators still in the scrollback are left
+        let offset = tile.stdout.evicted();
+        assert_eq!(tile.sections.len(), 3);
+        assert!(tile.sections.iter().all(|s| s.end >= offset));
+        assert_eq!(tile.view_len(), tile.stdout.len());
+
+        tile.set_filter(Some(Filter::new(Regex::new("a").unwrap())));
+        assert_eq!(tile.view_len(), 0);
+        assert_eq!(tile.scroll, 0);
+
+        tile.set_filter(None);
+        assert_eq!(tile.view_len(), tile.stdout.len());
+        assert_eq!(tile.scroll, tile.max_scroll());
+        assert_eq!(tile.view_line(0), Some(0));
     }
 }
@@ -1,7 +1,158 @@
 //! Some helper functions.
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use termion::cursor;
 
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+/// The number of columns between two tab stops.
+pub const TAB_WIDTH: u16 = 8;
+
+/// Returns the column of the first tab stop strictly after `column`.
+pub fn next_tab_stop(column: u16) -> u16 {
+    (column / TAB_WIDTH + 1) * TAB_WIDTH
+}
+
+/// Returns the number of columns taken by a grapheme cluster.
+///
+/// The width of a cluster is the width of its first character, except for emoji presentation
+/// sequences and flags which are always wide.
+pub fn grapheme_width(grapheme: &str) -> u16 {
+    let mut chars = grapheme.chars();
+
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return 0,
+    };
+
+    let is_regional_indicator = ('\u{1f1e6}'..='\u{1f1ff}').contains(&first);
+
+    if grapheme.contains('\u{fe0f}') || (is_regional_indicator && chars.next().is_some()) {
+        return 2;
+    }
+
+    UnicodeWidthChar::width(first).unwrap_or(0) as u16
+}
+
+/// Returns the number of columns taken by a string without escape sequences.
+pub fn text_width(s: &str) -> u16 {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Truncates a string without escape sequences so that it fits in `width` columns, replacing the
+/// end with `...` if needed.
+pub fn truncate(s: &str, width: u16) -> String {
+    if text_width(s) <= width {
+        return s.to_string();
+    }
+
+    let mut output = String::new();
+    let mut current = 0;
+
+    for grapheme in s.graphemes(true) {
+        let w = grapheme_width(grapheme);
+        if current + w + 3 > width {
+            break;
+        }
+        output.push_str(grapheme);
+        current += w;
+    }
+
+    output.push_str("...");
+    output
+}
+
+/// Returns the local date and time of a timestamp.
+fn local_time(time: SystemTime) -> libc::tm {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = duration.as_secs() as libc::time_t;
+
+    // SAFETY: `localtime_r` only writes into the `tm` we give it, which is valid for writes, and
+    // an all-zero `tm` is a valid value of this plain C struct
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&seconds, &mut tm);
+        tm
+    }
+}
+
+/// Formats the local time of a timestamp, e.g. `13:37:42.123`.
+pub fn format_time(time: SystemTime) -> String {
+    let tm = local_time(time);
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_millis();
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        tm.tm_hour, tm.tm_min, tm.tm_sec, millis
+    )
+}
+
+/// Formats the local date and time of a timestamp, e.g. `2024-03-14 13:37:42`.
+pub fn format_date_time(time: SystemTime) -> String {
+    let tm = local_time(time);
+
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// Formats the local date and time of a timestamp for a file name, e.g. `20240314-133742`.
+pub fn format_file_time(time: SystemTime) -> String {
+    format_date_time(time)
+        .replace(['-', ':'], "")
+        .replace(' ', "-")
+}
+
+/// Waits until there is something to read on stdin, and returns whether there is.
+pub fn wait_for_input(timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    // SAFETY: `poll` only reads and writes the single `pollfd` we give it, which is valid
+    let ready = unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) };
+    ready > 0
+}
+
+/// Encodes bytes in base64, with padding.
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
 /// Draws a box from (x1, y1) to (x2, y2).
 pub fn rect((x1, y1): (u16, u16), (x2, y2): (u16, u16)) -> String {
     let mut buffer = vec![];
@@ -9,10 +160,10 @@ pub fn rect((x1, y1): (u16, u16), (x2, y2): (u16, u16)) -> String {
     buffer.push(format!("{}┌", cursor::Goto(x1, y1)));
 
     for _ in (x1 + 1)..x2 {
-        buffer.push(format!("─"));
+        buffer.push("─".to_string());
     }
 
-    buffer.push(format!("┐"));
+    buffer.push("┐".to_string());
 
     for y in (y1 + 1)..y2 {
         buffer.push(format!("{}│", cursor::Goto(x1, y)));
@@ -22,10 +173,10 @@ pub fn rect((x1, y1): (u16, u16), (x2, y2): (u16, u16)) -> String {
     buffer.push(format!("{}└", cursor::Goto(x1, y2)));
 
     for _ in (x1 + 1)..x2 {
-        buffer.push(format!("─"));
+        buffer.push("─".to_string());
     }
 
-    buffer.push(format!("┘"));
+    buffer.push("┘".to_string());
 
     buffer.join("")
 }
@@ -36,12 +187,7 @@ pub fn str_len(s: &str) -> u16 {
     let mut counting = true;
     let mut iter = s.chars().peekable();
 
-    loop {
-        let current = match iter.next() {
-            Some(c) => c,
-            None => break,
-        };
-
+    while let Some(current) = iter.next() {
         let next = iter.peek();
 
         if current == '\x1b' && next == Some(&'[') {
@@ -61,7 +207,7 @@ pub fn str_len(s: &str) -> u16 {
 }
 
 /// Returns a substring of a string containing colors and styles.
-pub fn sub_str<'a>(s: &'a str, start: u16, end: u16) -> &'a str {
+pub fn sub_str(s: &str, start: u16, end: u16) -> &str {
     let mut counting = true;
     let mut iter = s.chars().peekable();
 
@@ -125,3 +271,41 @@ pub fn sub_str<'a>(s: &'a str, start: u16, end: u16) -> &'a str {
 
     &s[real_start..real_end]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn grapheme_widths() {
+        assert_eq!(grapheme_width(""), 0);
+        assert_eq!(grapheme_width("a"), 1);
+        assert_eq!(grapheme_width("中"), 2);
+
+        // Combining marks take no column, variation selectors make emojis wide
+        assert_eq!(grapheme_width("e\u{301}"), 1);
+        assert_eq!(grapheme_width("❤"), 1);
+        assert_eq!(grapheme_width("❤\u{fe0f}"), 2);
+
+        // A family joined by zero width joiners and a flag are a single emoji
+        assert_eq!(grapheme_width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(grapheme_width("🇫🇷"), 2);
+    }
+
+    #[test]
+    fn base64_alphabet() {
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+}